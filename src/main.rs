@@ -1,9 +1,20 @@
 use anyhow::{Context, Result, bail};
-use clap::{Parser, Subcommand};
-use lopdf::{Dictionary, Document, Object, ObjectId};
-use std::collections::{HashMap, HashSet};
+use chrono::Local;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use console::style;
+use lopdf::encryption::crypt_filters::{Aes128CryptFilter, Aes256CryptFilter, CryptFilter};
+use lopdf::xref::XrefType;
+use lopdf::{
+    Dictionary, Document, EncryptionState, EncryptionVersion, Object, ObjectId, Permissions,
+    Stream, StringFormat, decode_text_string, dictionary, text_string,
+};
+use md5::{Digest, Md5};
+use regex::RegexBuilder;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 fn collect_pdfs_recursive(dir: &Path, pdfs: &mut Vec<PathBuf>) -> Result<()> {
     if !dir.is_dir() {
@@ -31,6 +42,41 @@ fn collect_pdfs_recursive(dir: &Path, pdfs: &mut Vec<PathBuf>) -> Result<()> {
     Ok(())
 }
 
+fn collect_input_pdfs(paths: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut pdf_files = Vec::new();
+    for path in paths {
+        if recursive && path.is_dir() {
+            collect_pdfs_recursive(path, &mut pdf_files)?;
+        } else if path.is_file() {
+            let is_pdf = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|ext| ext.eq_ignore_ascii_case("pdf"))
+                .unwrap_or(false);
+
+            if is_pdf {
+                pdf_files.push(path.clone());
+            } else {
+                bail!("Non-PDF file provided: {}", path.display());
+            }
+        } else if path.is_dir() {
+            bail!(
+                "'{}' is a directory. Use -r/--recursive to search subdirectories",
+                path.display()
+            );
+        } else {
+            bail!("Invalid path: {}", path.display());
+        }
+    }
+
+    if pdf_files.is_empty() {
+        bail!("No PDF files found");
+    }
+
+    pdf_files.sort();
+    Ok(pdf_files)
+}
+
 #[derive(Parser)]
 #[command(name = "pdfer")]
 #[command(
@@ -41,13 +87,16 @@ fn collect_pdfs_recursive(dir: &Path, pdfs: &mut Vec<PathBuf>) -> Result<()> {
                   Examples:\n\
                   • Quick info:  pdfer test.pdf\n\
                   • Merge:       pdfer merge a.pdf b.pdf -o out.pdf\n\
-                  • Split:       pdfer split doc.pdf 1,3,5-10"
+                  • Split:       pdfer split doc.pdf 1,3,5-10\n\
+                  • Search:      pdfer grep invoice -r ./archive"
 )]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    #[arg(global = true)]
+    /// PDFs to summarize when no subcommand is given
+    // Not global: clap rejects a global positional alongside the
+    // subcommands' own positional arguments (e.g. merge's INPUTS).
     files: Vec<PathBuf>,
 
     #[arg(short, long, global = true)]
@@ -55,13 +104,129 @@ struct Cli {
 
     #[arg(short, long, global = true)]
     recursive: bool,
+
+    #[command(flatten)]
+    password: PasswordArgs,
+}
+
+/// Accepted by every command; all inputs are opened with this password.
+#[derive(Args)]
+struct PasswordArgs {
+    /// Password for encrypted inputs (also read from PDFER_PASSWORD)
+    #[arg(long, global = true)]
+    password: Option<String>,
+
+    /// Read the password from the first line of FILE
+    #[arg(long, value_name = "FILE", global = true, conflicts_with = "password")]
+    password_file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct EncryptArgs {
+    /// Password required to open the output
+    #[arg(long, value_name = "PASSWORD")]
+    user_password: Option<String>,
+
+    /// Password that lifts the restrictions (required with --allow)
+    #[arg(long, value_name = "PASSWORD")]
+    owner_password: Option<String>,
+
+    /// Comma-separated permissions: all, none, print, print-high, copy,
+    /// modify, annotate, fill-forms, assemble
+    #[arg(long, value_name = "LIST", default_value = "all")]
+    allow: String,
+
+    /// Use AES-128 instead of AES-256 for older readers
+    #[arg(long)]
+    aes128: bool,
+}
+
+#[derive(Args)]
+struct OutputEncryptionArgs {
+    /// Password-protect the output (see --user-password, --owner-password, --allow)
+    #[arg(long)]
+    encrypt: bool,
+
+    #[command(flatten)]
+    settings: EncryptArgs,
+}
+
+#[derive(Args)]
+struct MetadataArgs {
+    #[arg(long)]
+    title: Option<String>,
+
+    #[arg(long)]
+    author: Option<String>,
+
+    #[arg(long)]
+    subject: Option<String>,
+}
+
+#[derive(Args)]
+struct CollateArgs {
+    /// Reverse the page order of input N (1-based, repeatable)
+    #[arg(long, value_name = "N")]
+    reverse: Vec<usize>,
+}
+
+#[derive(Args)]
+struct MergeOptions {
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    metadata_from: usize,
+
+    #[arg(long, value_name = "VERSION")]
+    pdf_version: Option<String>,
+
+    #[arg(long, value_name = "blank|FILE")]
+    separator: Option<String>,
+
+    /// Size of blank separators (default: the preceding page's size)
+    #[arg(long, value_name = "SIZE", requires = "separator")]
+    separator_size: Option<String>,
+
+    /// Alternate pages from the inputs instead of appending them
+    #[arg(long, conflicts_with = "separator")]
+    interleave: bool,
+
+    #[arg(long, requires = "interleave")]
+    allow_uneven: bool,
+
+    /// Scale every page to this paper size (e.g. A4, Letter, 210x297mm)
+    #[arg(long, value_name = "SIZE")]
+    normalize_size: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "fit|fill|center",
+        default_value = "fit",
+        requires = "normalize_size"
+    )]
+    normalize_mode: String,
+
+    /// Add one bookmark per input, titled from its /Title or file name
+    #[arg(long)]
+    bookmarks: bool,
+
+    /// Keep each input's page labels (inputs without labels restart at 1)
+    #[arg(long, conflicts_with = "interleave")]
+    labels: bool,
+
+    #[command(flatten)]
+    collate: CollateArgs,
+
+    #[command(flatten)]
+    metadata: MetadataArgs,
+
+    #[command(flatten)]
+    encryption: OutputEncryptionArgs,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     #[command(
         visible_alias = "m",
-        after_help = "Examples:\n  pdfer merge a.pdf b.pdf -o out.pdf\n  pdfer m *.pdf -o merged.pdf"
+        after_help = "Examples:\n  pdfer merge a.pdf b.pdf -o out.pdf\n  pdfer m *.pdf -o merged.pdf\n  pdfer merge a.pdf b.pdf --metadata-from 2 --title \"Annual Report\"\n  pdfer merge ch1.pdf ch2.pdf --separator blank"
     )]
     Merge {
         #[arg(required = true)]
@@ -69,6 +234,36 @@ enum Commands {
 
         #[arg(short, long, default_value = "merged.pdf")]
         output: PathBuf,
+
+        #[command(flatten)]
+        options: MergeOptions,
+    },
+
+    #[command(
+        visible_alias = "c",
+        after_help = "Examples:\n  pdfer collate fronts.pdf backs.pdf --reverse 2 -o book.pdf\n  pdfer c a.pdf b.pdf c.pdf --allow-uneven"
+    )]
+    Collate {
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+
+        #[arg(short, long, default_value = "collated.pdf")]
+        output: PathBuf,
+
+        #[arg(long, value_name = "VERSION")]
+        pdf_version: Option<String>,
+
+        #[command(flatten)]
+        collate: CollateArgs,
+
+        #[arg(long)]
+        allow_uneven: bool,
+
+        #[command(flatten)]
+        metadata: MetadataArgs,
+
+        #[command(flatten)]
+        encryption: OutputEncryptionArgs,
     },
 
     #[command(
@@ -83,372 +278,6523 @@ enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
 
+        #[arg(long, value_name = "VERSION")]
+        pdf_version: Option<String>,
+
+        #[command(flatten)]
+        metadata: MetadataArgs,
+
+        #[command(flatten)]
+        encryption: OutputEncryptionArgs,
+
         #[arg(hide = true, trailing_var_arg = true)]
         extra_args: Vec<String>,
     },
-}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+    #[command(
+        visible_alias = "g",
+        after_help = "Examples:\n  pdfer grep invoice report.pdf\n  pdfer grep 'total:\\s*\\d+' -r ./archive\n  pdfer g --ignore-case -l contract -r ./archive"
+    )]
+    Grep {
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
 
-    if cli.command.is_none() {
-        if cli.files.is_empty() {
-            bail!(
-                "No PDF files or command specified. Try 'pdfer --help' or 'pdfer <file.pdf>' for quick info"
-            );
-        }
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
 
-        let mut pdf_files = Vec::new();
-        for path in &cli.files {
-            if cli.recursive && path.is_dir() {
-                collect_pdfs_recursive(path, &mut pdf_files)?;
-            } else if path.is_file() {
-                let is_pdf = path
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .map(|ext| ext.eq_ignore_ascii_case("pdf"))
-                    .unwrap_or(false);
+        #[arg(long)]
+        ignore_case: bool,
 
-                if is_pdf {
-                    pdf_files.push(path.clone());
-                } else {
-                    bail!("Non-PDF file provided: {}", path.display());
-                }
-            } else if path.is_dir() {
-                bail!(
-                    "'{}' is a directory. Use -r/--recursive to search subdirectories",
-                    path.display()
-                );
-            } else {
-                bail!("Invalid path: {}", path.display());
-            }
-        }
+        #[arg(short = 'l', long)]
+        files_with_matches: bool,
+    },
 
-        if pdf_files.is_empty() {
-            bail!("No PDF files found");
-        }
+    #[command(
+        after_help = "Examples:\n  pdfer meta doc.pdf                                # Print all metadata\n  pdfer meta doc.pdf --set Title=\"Q3 Report\" -o out.pdf\n  pdfer meta doc.pdf --delete Keywords --set ModDate=now\n  pdfer meta doc.pdf --export meta.json\n  pdfer meta doc.pdf --import meta.json -o out.pdf"
+    )]
+    Meta {
+        input: PathBuf,
 
-        let mut total_pages = 0;
-        pdf_files.sort();
-        for file in &pdf_files {
-            match show_pdf_info(file) {
-                Ok(page_count) => total_pages += page_count,
-                Err(e) => eprintln!("Error reading {}: {}", file.display(), e),
-            }
-            if pdf_files.len() > 1 {
-                println!();
-            }
-        }
+        #[arg(long, value_name = "KEY=VALUE")]
+        set: Vec<String>,
 
-        if pdf_files.len() > 1 {
-            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-            println!("Total: {} PDF(s), {} page(s)", pdf_files.len(), total_pages);
-        }
+        #[arg(long, value_name = "KEY")]
+        delete: Vec<String>,
 
-        return Ok(());
-    }
+        #[arg(long, value_name = "FILE")]
+        import: Option<PathBuf>,
 
-    match cli.command.unwrap() {
-        Commands::Merge { inputs, output } => {
-            if cli.info {
-                for input in &inputs {
-                    let _ = show_pdf_info(input);
-                    println!();
-                }
-            }
-            merge_pdfs(&inputs, &output)?
-        }
-        Commands::Split {
-            input,
-            pages,
-            output,
-            extra_args,
-        } => {
-            if !extra_args.is_empty() {
-                bail!(
-                    "Split command accepts only ONE input PDF file.\n\
-                     Found extra arguments: {}\n\
-                     \n\
-                     ⚠️ If your filename contains spaces, wrap it in quotes:\n\
-                        pdfer split \"file with spaces.pdf\"\n\
-                     \n\
-                     Usage: pdfer split <file.pdf> [PAGES] [-o OUTPUT]\n\
-                     \n\
-                     To split multiple PDFs, run the command separately for each:\n\
-                       pdfer split a.pdf\n\
-                       pdfer split b.pdf",
-                    extra_args.join(", ")
-                );
-            }
+        #[arg(long, value_name = "FILE")]
+        export: Option<PathBuf>,
 
-            if cli.info {
-                let _ = show_pdf_info(&input);
-                println!();
-            }
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 
-            let output = output.unwrap_or_else(|| {
-                let stem = input
-                    .file_stem()
-                    .map(|s| s.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "split".to_string());
-                PathBuf::from(format!("{}_pages", stem))
-            });
-            split_pdf(&input, &output, pages.as_deref())?
-        }
-    }
-    Ok(())
-}
+    #[command(
+        after_help = "Removes /Info, XMP metadata, JavaScript, embedded files, /PieceInfo,\n\
+                      thumbnails and form submit actions.\n\n\
+                      Examples:\n  pdfer sanitize report.pdf\n  pdfer sanitize report.pdf -o clean.pdf"
+    )]
+    Sanitize {
+        input: PathBuf,
 
-fn show_pdf_info(path: &Path) -> Result<usize> {
-    let doc =
-        Document::load(path).with_context(|| format!("Failed to load PDF: {}", path.display()))?;
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 
-    let page_count = doc.get_pages().len();
+    #[command(
+        visible_alias = "r",
+        after_help = "Examples:\n  pdfer rotate scan.pdf 90                 # Rotate all pages clockwise\n  pdfer rotate scan.pdf 180 2,4-6          # Rotate selected pages\n  pdfer rotate scan.pdf 0 --absolute       # Reset rotation\n  pdfer r scan.pdf -90 3 -o fixed.pdf      # Rotate counter-clockwise"
+    )]
+    Rotate {
+        input: PathBuf,
 
-    println!("📄 {}", path.display());
-    println!("   Pages: {}", page_count);
-    println!("   Version: {}", doc.version);
+        #[arg(value_name = "ANGLE", allow_hyphen_values = true)]
+        angle: String,
 
-    if let Ok(info_dict) = doc.trailer.get(b"Info") {
-        if let Object::Reference(id) = info_dict {
-            if let Ok(Object::Dictionary(info)) = doc.get_object(*id) {
-                if let Ok(Object::String(title, _)) = info.get(b"Title") {
-                    println!("   Title: {}", String::from_utf8_lossy(title));
-                }
-                if let Ok(Object::String(author, _)) = info.get(b"Author") {
-                    println!("   Author: {}", String::from_utf8_lossy(author));
-                }
-                if let Ok(Object::String(subject, _)) = info.get(b"Subject") {
-                    println!("   Subject: {}", String::from_utf8_lossy(subject));
-                }
-            }
-        }
-    }
+        #[arg(value_name = "PAGES")]
+        pages: Option<String>,
 
-    let pages = doc.get_pages();
-    let page_nums: Vec<u32> = pages.keys().copied().collect();
-    if !page_nums.is_empty() {
-        let mut sorted = page_nums.clone();
-        sorted.sort_unstable();
-        if sorted.len() <= 10 {
-            println!("   Page numbers: {:?}", sorted);
-        } else {
-            println!(
-                "   Page numbers: {} to {}",
-                sorted[0],
-                sorted[sorted.len() - 1]
-            );
-        }
-    }
+        #[arg(long)]
+        absolute: bool,
 
-    Ok(page_count)
-}
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 
-fn parse_page_ranges(spec: &str, total_pages: usize) -> Result<Vec<usize>> {
-    if total_pages == 0 {
-        bail!("PDF has no pages");
-    }
+    #[command(
+        visible_alias = "d",
+        after_help = "Examples:\n  pdfer delete doc.pdf 3,7-9 -o out.pdf\n  pdfer d doc.pdf 1               # Drop the cover page"
+    )]
+    Delete {
+        input: PathBuf,
 
-    let mut pages = Vec::new();
-    for part in spec.split(',') {
-        let part = part.trim();
-        if part.is_empty() {
-            continue;
-        }
+        #[arg(value_name = "PAGES")]
+        pages: String,
 
-        if part.contains('-') {
-            let bounds: Vec<&str> = part.split('-').collect();
-            if bounds.len() != 2 {
-                bail!("Invalid range syntax: '{}'", part);
-            }
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 
-            let start_str = bounds[0].trim();
-            let end_str = bounds[1].trim();
+    #[command(
+        after_help = "Examples:\n  pdfer reorder doc.pdf 5-10,1-4,11-      # Move pages 5-10 to the front\n  pdfer reorder doc.pdf --reverse\n  pdfer reorder doc.pdf 1,2,1,3 --allow-duplicates"
+    )]
+    Reorder {
+        input: PathBuf,
 
-            if start_str.is_empty() {
-                bail!("Invalid range: '{}' (page numbers must be >= 1)", part);
-            }
+        #[arg(value_name = "ORDER", required_unless_present = "reverse")]
+        order: Option<String>,
 
-            let start = start_str
-                .parse::<usize>()
-                .map_err(|_| anyhow::anyhow!("Invalid page number: '{}'", start_str))?;
+        #[arg(long)]
+        reverse: bool,
 
-            let end = if end_str.is_empty() {
-                total_pages
-            } else {
-                end_str
-                    .parse::<usize>()
-                    .map_err(|_| anyhow::anyhow!("Invalid page number: '{}'", end_str))?
-            };
+        #[arg(long)]
+        allow_duplicates: bool,
 
-            if start < 1 {
-                bail!("Page numbers must be >= 1");
-            }
-            if start > total_pages {
-                bail!(
-                    "Start page {} is beyond document end ({})",
-                    start,
-                    total_pages
-                );
-            }
-            if !end_str.is_empty() && end > total_pages {
-                bail!("End page {} is beyond document end ({})", end, total_pages);
-            }
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 
-            let actual_end = end.min(total_pages);
-            if start <= actual_end {
-                pages.extend(start..=actual_end);
-            } else {
+    #[command(
+        after_help = "Examples:\n  pdfer insert base.pdf --at 5 --from other.pdf\n  pdfer insert base.pdf --after 3 --from appendix.pdf:2-3\n  pdfer insert base.pdf --before 1 --from cover.pdf:1"
+    )]
+    #[command(group(ArgGroup::new("position").required(true).args(["at", "before", "after"])))]
+    Insert {
+        input: PathBuf,
+
+        #[arg(long, value_name = "FILE[:PAGES]")]
+        from: String,
+
+        #[arg(long, value_name = "N")]
+        at: Option<usize>,
+
+        #[arg(long, value_name = "N")]
+        before: Option<usize>,
+
+        #[arg(long, value_name = "N")]
+        after: Option<usize>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Examples:\n  pdfer blank doc.pdf --before 1                 # Blank inside cover\n  pdfer blank doc.pdf --after 4,9 --size A4\n  pdfer blank book.pdf --odd-chapters            # Chapters from bookmarks\n  pdfer blank book.pdf --odd-chapters 1,5,12     # Explicit chapter starts"
+    )]
+    #[command(group(ArgGroup::new("where").required(true).multiple(true).args(["before", "after", "odd_chapters"])))]
+    Blank {
+        input: PathBuf,
+
+        #[arg(long, value_name = "PAGES")]
+        before: Option<String>,
+
+        #[arg(long, value_name = "PAGES")]
+        after: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "STARTS",
+            num_args = 0..=1,
+            default_missing_value = "outline"
+        )]
+        odd_chapters: Option<String>,
+
+        #[arg(long, value_name = "SIZE")]
+        size: Option<String>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Margins and sizes follow the page as displayed (its /Rotate is applied);\n\
+                      --rect uses unrotated PDF coordinates.\n\n\
+                      Examples:\n  pdfer crop scan.pdf --margins 10mm                   # Trim 10mm on every side\n  pdfer crop scan.pdf 2-5 --margins 0.5in,0.25in\n  pdfer crop book.pdf --box trim --size A5\n  pdfer crop art.pdf --box media --box crop --rect 0,0,400,600"
+    )]
+    #[command(group(ArgGroup::new("geometry").required(true).args(["rect", "margins", "size"])))]
+    Crop {
+        input: PathBuf,
+
+        #[arg(value_name = "PAGES")]
+        pages: Option<String>,
+
+        /// Page box to set: media, crop, trim, bleed or art (repeatable)
+        #[arg(long = "box", value_name = "BOX", default_value = "crop")]
+        boxes: Vec<String>,
+
+        #[arg(long, value_name = "X0,Y0,X1,Y1")]
+        rect: Option<String>,
+
+        #[arg(long, value_name = "TOP[,RIGHT,BOTTOM,LEFT]")]
+        margins: Option<String>,
+
+        #[arg(long, value_name = "SIZE")]
+        size: Option<String>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Page content is scaled with its aspect ratio preserved:\n  fit     whole page visible, centered (default)\n  fill    sheet fully covered, overflow clipped\n  center  no scaling, page centered on the sheet\n\n\
+                      Examples:\n  pdfer resize mixed.pdf --size A4\n  pdfer resize scans.pdf --size Letter --mode fill\n  pdfer resize slides.pdf 2- --size A4 --keep-orientation\n  pdfer merge a.pdf b.pdf --normalize-size A4 -o out.pdf"
+    )]
+    Resize {
+        input: PathBuf,
+
+        #[arg(value_name = "PAGES")]
+        pages: Option<String>,
+
+        #[arg(long, value_name = "SIZE")]
+        size: String,
+
+        #[arg(long, value_name = "fit|fill|center", default_value = "fit")]
+        mode: String,
+
+        /// Use the landscape variant of SIZE for landscape pages
+        #[arg(long)]
+        keep_orientation: bool,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Reading orders: row (default), column, row-rtl, column-rtl.\n\
+                      Without --sheet the first page's size is used, turned to fit the grid best.\n\n\
+                      Examples:\n  pdfer nup doc.pdf --grid 2x2 -o handout.pdf\n  pdfer nup slides.pdf --grid 2x3 --sheet A4 --margin 10mm --gutter 5mm --frame\n  pdfer nup doc.pdf 1-8 --grid 2x1 --sheet A4-landscape --order row-rtl"
+    )]
+    Nup {
+        input: PathBuf,
+
+        #[arg(value_name = "PAGES")]
+        pages: Option<String>,
+
+        #[arg(long, value_name = "COLSxROWS")]
+        grid: String,
+
+        #[arg(long, value_name = "SIZE")]
+        sheet: Option<String>,
+
+        #[arg(long, value_name = "LENGTH", default_value = "0")]
+        margin: String,
+
+        #[arg(long, value_name = "LENGTH", default_value = "0")]
+        gutter: String,
+
+        #[arg(long, value_name = "ORDER", default_value = "row")]
+        order: String,
+
+        /// Draw a border around each page (optional line width in pt)
+        #[arg(long, value_name = "WIDTH", num_args = 0..=1, default_missing_value = "0.5")]
+        frame: Option<String>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Print the output double-sided (flip on short edge), fold and staple.\n\
+                      Without --sheet each sheet is two source pages wide.\n\n\
+                      Examples:\n  pdfer booklet zine.pdf\n  pdfer booklet manual.pdf --sheet A4-landscape --signature 16 --creep 0.2mm\n  pdfer booklet manga.pdf --rtl"
+    )]
+    Booklet {
+        input: PathBuf,
+
+        #[arg(value_name = "PAGES")]
+        pages: Option<String>,
+
+        #[arg(long, value_name = "SIZE")]
+        sheet: Option<String>,
+
+        /// Pages per folded signature (multiple of 4; default: one signature)
+        #[arg(long, value_name = "PAGES")]
+        signature: Option<usize>,
+
+        /// Shift towards the spine added for each sheet further inside
+        #[arg(long, value_name = "LENGTH", default_value = "0")]
+        creep: String,
+
+        /// Bind on the right (right-to-left reading)
+        #[arg(long)]
+        rtl: bool,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Tiles run left to right, top to bottom. Pages outside the selection are kept as they are.\n\n\
+                      Examples:\n  pdfer tile plan.pdf --sheet A4 --overlap 10mm --marks\n  pdfer tile drawings.pdf 2 --sheet A3-landscape --scale 0.5\n  pdfer tile poster.pdf --sheet Letter --margin 0.5in"
+    )]
+    Tile {
+        input: PathBuf,
+
+        #[arg(value_name = "PAGES")]
+        pages: Option<String>,
+
+        #[arg(long, value_name = "SIZE", default_value = "A4")]
+        sheet: String,
+
+        /// Unprinted border kept on every sheet
+        #[arg(long, value_name = "LENGTH", default_value = "10mm")]
+        margin: String,
+
+        /// Content repeated on neighbouring tiles to help alignment
+        #[arg(long, value_name = "LENGTH", default_value = "0")]
+        overlap: String,
+
+        #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+        scale: f32,
+
+        /// Draw crop marks at the corners of each tile
+        #[arg(long)]
+        marks: bool,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Positions: center, top, bottom, left, right, top-left, top-right, bottom-left, bottom-right.\n\
+                      Rotation is counter-clockwise in degrees.\n\n\
+                      Examples:\n  pdfer stamp out.pdf --text CONFIDENTIAL --rotate 45 --opacity 0.3\n  pdfer stamp out.pdf 1 --image logo.png --width 1in --position top-right\n  pdfer stamp out.pdf --text DRAFT --font Times-Bold --font-size 96 --color FF0000 --underlay\n  pdfer stamp out.pdf --pdf seal.pdf:1 --position bottom-left --margin 20mm"
+    )]
+    #[command(group(ArgGroup::new("source").required(true).args(["text", "image", "pdf"])))]
+    Stamp {
+        input: PathBuf,
+
+        #[arg(value_name = "PAGES")]
+        pages: Option<String>,
+
+        #[arg(long, value_name = "TEXT")]
+        text: Option<String>,
+
+        /// PNG or JPEG image to stamp
+        #[arg(long, value_name = "FILE")]
+        image: Option<PathBuf>,
+
+        /// PDF page to stamp (first page unless :PAGE is given)
+        #[arg(long, value_name = "FILE[:PAGE]")]
+        pdf: Option<String>,
+
+        /// Draw the stamp behind the page content
+        #[arg(long)]
+        underlay: bool,
+
+        #[arg(long, value_name = "0-1", default_value_t = 1.0)]
+        opacity: f32,
+
+        #[arg(
+            long,
+            value_name = "DEGREES",
+            default_value_t = 0.0,
+            allow_hyphen_values = true
+        )]
+        rotate: f32,
+
+        #[arg(long, value_name = "POSITION", default_value = "center")]
+        position: String,
+
+        #[arg(long, value_name = "LENGTH", default_value = "36")]
+        margin: String,
+
+        #[arg(long, value_name = "FONT", default_value = "Helvetica-Bold")]
+        font: String,
+
+        #[arg(long, value_name = "PT", default_value_t = 48.0)]
+        font_size: f32,
+
+        #[arg(long, value_name = "RRGGBB", default_value = "808080")]
+        color: String,
+
+        /// Width of an image or PDF stamp (height keeps the aspect ratio)
+        #[arg(long, value_name = "LENGTH")]
+        width: Option<String>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "By default page N gets overlay page N. Overlay pages are scaled to fit when sizes differ.\n\n\
+                      Examples:\n  pdfer overlay invoice.pdf --background letterhead.pdf --repeat\n  pdfer overlay report.pdf --background paper.pdf --map 1:1,2-:2\n  pdfer overlay form.pdf --foreground answers.pdf\n  pdfer overlay deck.pdf --background pattern.pdf --cycle"
+    )]
+    #[command(group(ArgGroup::new("layer").required(true).multiple(true).args(["background", "foreground"])))]
+    #[command(group(ArgGroup::new("mapping").args(["repeat", "cycle", "map"])))]
+    Overlay {
+        input: PathBuf,
+
+        /// PDF drawn behind each page (e.g. letterhead)
+        #[arg(long, value_name = "FILE")]
+        background: Option<PathBuf>,
+
+        /// PDF drawn on top of each page
+        #[arg(long, value_name = "FILE")]
+        foreground: Option<PathBuf>,
+
+        /// Use the overlay's first page on every page
+        #[arg(long)]
+        repeat: bool,
+
+        /// Reuse the overlay pages in turn
+        #[arg(long)]
+        cycle: bool,
+
+        /// Explicit mapping of target pages to overlay pages
+        #[arg(long, value_name = "PAGES:N,...")]
+        map: Option<String>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Placeholders: {page}, {total}, {label}, {filename}, {date}.\n\
+                      --start sets the number of the first page; skipped pages keep their number but show nothing.\n\n\
+                      Examples:\n  pdfer number merged.pdf\n  pdfer number report.pdf --footer \"Page {page} of {total}\" --align right\n  pdfer number book.pdf --skip 1 --start 0 --header \"{filename}\" --footer \"{label}\"\n  pdfer number memo.pdf --footer \"{date}\" --font Times-Roman --font-size 9 --margin 15mm"
+    )]
+    Number {
+        input: PathBuf,
+
+        #[arg(long, value_name = "TEXT")]
+        header: Option<String>,
+
+        /// Footer text (defaults to "{page}" when no header is given)
+        #[arg(long, value_name = "TEXT")]
+        footer: Option<String>,
+
+        #[arg(long, value_name = "left|center|right", default_value = "center")]
+        align: String,
+
+        /// Distance of the text from the page edge
+        #[arg(long, value_name = "LENGTH", default_value = "0.5in")]
+        margin: String,
+
+        #[arg(long, value_name = "FONT", default_value = "Helvetica")]
+        font: String,
+
+        #[arg(long, value_name = "PT", default_value_t = 10.0)]
+        font_size: f32,
+
+        #[arg(long, value_name = "RRGGBB", default_value = "000000")]
+        color: String,
+
+        /// Number shown on the first page
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 1,
+            allow_hyphen_values = true
+        )]
+        start: i64,
+
+        /// Pages left without header or footer (e.g. the cover)
+        #[arg(long, value_name = "PAGES")]
+        skip: Option<String>,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Files are numbered in sorted path order with one running counter.\n\n\
+                      Examples:\n  pdfer bates -r ./production --prefix ABC --digits 6 -o stamped\n  pdfer bates a.pdf b.pdf --prefix DEF- --start 1001 --position bottom-left\n  pdfer bates -r ./volume2 --prefix ABC --continue-from stamped/bates_log.csv -o stamped2"
+    )]
+    Bates {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        #[arg(long, default_value = "")]
+        prefix: String,
+
+        #[arg(long, default_value = "")]
+        suffix: String,
+
+        /// Minimum number of digits (zero-padded)
+        #[arg(long, value_name = "N", default_value_t = 6)]
+        digits: usize,
+
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        start: u64,
+
+        /// Continue after the last number in a previous run's log
+        #[arg(long, value_name = "LOG", conflicts_with = "start")]
+        continue_from: Option<PathBuf>,
+
+        #[arg(long, value_name = "POSITION", default_value = "bottom-right")]
+        position: String,
+
+        #[arg(long, value_name = "LENGTH", default_value = "0.5in")]
+        margin: String,
+
+        #[arg(long, value_name = "FONT", default_value = "Helvetica")]
+        font: String,
+
+        #[arg(long, value_name = "PT", default_value_t = 10.0)]
+        font_size: f32,
+
+        #[arg(long, value_name = "RRGGBB", default_value = "000000")]
+        color: String,
+
+        /// CSV log of each file's first and last number (default: OUTPUT/bates_log.csv)
+        #[arg(long, value_name = "FILE")]
+        log: Option<PathBuf>,
+
+        #[arg(short, long, default_value = "bates")]
+        output: PathBuf,
+    },
+
+    #[command(
+        after_help = "Text format: one bookmark per line, indented two spaces per level:\n  Title | PAGE [XYZ LEFT TOP ZOOM | FitH TOP | ...] | bold italic #RRGGBB open\n  Title | URI https://example.com\n\
+                      Files ending in .json (or --json) use JSON instead.\n\n\
+                      Examples:\n  pdfer outline book.pdf                          # Print the bookmarks\n  pdfer outline book.pdf --export toc.txt\n  pdfer outline book.pdf --import toc.txt -o fixed.pdf\n  pdfer outline scan.pdf --import extra.json --append"
+    )]
+    Outline {
+        input: PathBuf,
+
+        #[arg(long, value_name = "FILE")]
+        import: Option<PathBuf>,
+
+        /// Add the imported bookmarks after the existing ones
+        #[arg(long, requires = "import")]
+        append: bool,
+
+        #[arg(long, value_name = "FILE")]
+        export: Option<PathBuf>,
+
+        /// Use JSON regardless of file extension
+        #[arg(long)]
+        json: bool,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Spec: comma-separated PAGES:STYLE[:PREFIX[:START]] ranges in page order.\n\
+                      Styles: D (1, 2, 3), r (i, ii), R (I, II), a (a, b), A (A, B), - (prefix only).\n\n\
+                      Examples:\n  pdfer labels book.pdf                           # Show the current labels\n  pdfer labels book.pdf 1-4:r,5-:D -o fixed.pdf\n  pdfer labels book.pdf 1:-:Cover,2-5:r,6-:D,120-:A:App-\n  pdfer labels book.pdf --clear"
+    )]
+    Labels {
+        input: PathBuf,
+
+        #[arg(value_name = "SPEC")]
+        spec: Option<String>,
+
+        /// Remove all page labels
+        #[arg(long, conflicts_with = "spec")]
+        clear: bool,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Examples:\n  pdfer decrypt locked.pdf --password secret\n  pdfer decrypt locked.pdf --password-file pw.txt -o open.pdf\n  PDFER_PASSWORD=secret pdfer decrypt locked.pdf"
+    )]
+    Decrypt {
+        input: PathBuf,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    #[command(
+        after_help = "Examples:\n  pdfer encrypt report.pdf --user-password open123 --owner-password admin\n  pdfer encrypt report.pdf --owner-password admin --allow print,copy\n  pdfer encrypt report.pdf --user-password open123 --aes128 -o legacy.pdf\n  pdfer merge a.pdf b.pdf --encrypt --user-password open123 -o out.pdf"
+    )]
+    Encrypt {
+        input: PathBuf,
+
+        #[command(flatten)]
+        settings: EncryptArgs,
+
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let password = resolve_password(&cli.password)?;
+
+    if cli.command.is_none() {
+        if cli.files.is_empty() {
+            bail!(
+                "No PDF files or command specified. Try 'pdfer --help' or 'pdfer <file.pdf>' for quick info"
+            );
+        }
+
+        let pdf_files = collect_input_pdfs(&cli.files, cli.recursive)?;
+
+        let mut total_pages = 0;
+        for file in &pdf_files {
+            match show_pdf_info(file, password.as_deref()) {
+                Ok(page_count) => total_pages += page_count,
+                Err(e) => eprintln!("Error reading {}: {}", file.display(), e),
+            }
+            if pdf_files.len() > 1 {
+                println!();
+            }
+        }
+
+        if pdf_files.len() > 1 {
+            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+            println!("Total: {} PDF(s), {} page(s)", pdf_files.len(), total_pages);
+        }
+
+        return Ok(());
+    }
+
+    match cli.command.unwrap() {
+        Commands::Merge {
+            inputs,
+            output,
+            options,
+        } => {
+            if cli.info {
+                for input in &inputs {
+                    let _ = show_pdf_info(input, password.as_deref());
+                    println!();
+                }
+            }
+            merge_pdfs(&inputs, &output, &options, password.as_deref())?
+        }
+        Commands::Collate {
+            inputs,
+            output,
+            pdf_version,
+            collate,
+            allow_uneven,
+            metadata,
+            encryption,
+        } => {
+            let options = MergeOptions {
+                metadata_from: 1,
+                pdf_version,
+                separator: None,
+                separator_size: None,
+                interleave: true,
+                allow_uneven,
+                normalize_size: None,
+                normalize_mode: "fit".to_string(),
+                bookmarks: false,
+                labels: false,
+                collate,
+                metadata,
+                encryption,
+            };
+            merge_pdfs(&inputs, &output, &options, password.as_deref())?
+        }
+        Commands::Split {
+            input,
+            pages,
+            output,
+            pdf_version,
+            metadata,
+            encryption,
+            extra_args,
+        } => {
+            if !extra_args.is_empty() {
+                bail!(
+                    "Split command accepts only ONE input PDF file.\n\
+                     Found extra arguments: {}\n\
+                     \n\
+                     ⚠️ If your filename contains spaces, wrap it in quotes:\n\
+                        pdfer split \"file with spaces.pdf\"\n\
+                     \n\
+                     Usage: pdfer split <file.pdf> [PAGES] [-o OUTPUT]\n\
+                     \n\
+                     To split multiple PDFs, run the command separately for each:\n\
+                       pdfer split a.pdf\n\
+                       pdfer split b.pdf",
+                    extra_args.join(", ")
+                );
+            }
+
+            if cli.info {
+                let _ = show_pdf_info(&input, password.as_deref());
+                println!();
+            }
+
+            let output = output.unwrap_or_else(|| {
+                let stem = input
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "split".to_string());
+                PathBuf::from(format!("{}_pages", stem))
+            });
+            split_pdf(
+                &input,
+                &output,
+                pages.as_deref(),
+                pdf_version.as_deref(),
+                &metadata,
+                password.as_deref(),
+                &encryption,
+            )?
+        }
+        Commands::Grep {
+            pattern,
+            paths,
+            ignore_case,
+            files_with_matches,
+        } => {
+            let pdf_files = collect_input_pdfs(&paths, cli.recursive)?;
+            let matches = grep_pdfs(
+                &pdf_files,
+                &pattern,
+                ignore_case,
+                files_with_matches,
+                password.as_deref(),
+            )?;
+            if matches == 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Meta {
+            input,
+            set,
+            delete,
+            import,
+            export,
+            output,
+        } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "meta"));
+            edit_metadata(
+                &input,
+                &output,
+                &set,
+                &delete,
+                import.as_deref(),
+                export.as_deref(),
+                password.as_deref(),
+            )?
+        }
+        Commands::Sanitize { input, output } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "sanitized"));
+            sanitize_pdf(&input, &output, password.as_deref())?
+        }
+        Commands::Rotate {
+            input,
+            angle,
+            pages,
+            absolute,
+            output,
+        } => {
+            let angle = parse_rotation_angle(&angle)?;
+            let output = output.unwrap_or_else(|| default_output(&input, "rotated"));
+            rotate_pdf(
+                &input,
+                &output,
+                angle,
+                absolute,
+                pages.as_deref(),
+                password.as_deref(),
+            )?
+        }
+        Commands::Delete {
+            input,
+            pages,
+            output,
+        } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "deleted"));
+            delete_pages(&input, &output, &pages, password.as_deref())?
+        }
+        Commands::Reorder {
+            input,
+            order,
+            reverse,
+            allow_duplicates,
+            output,
+        } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "reordered"));
+            reorder_pages(
+                &input,
+                &output,
+                order.as_deref(),
+                reverse,
+                allow_duplicates,
+                password.as_deref(),
+            )?
+        }
+        Commands::Insert {
+            input,
+            from,
+            at,
+            before,
+            after,
+            output,
+        } => {
+            let position = at.or(before).unwrap_or_else(|| after.unwrap_or(0) + 1);
+            let output = output.unwrap_or_else(|| default_output(&input, "inserted"));
+            insert_pages(&input, &output, position, &from, password.as_deref())?
+        }
+        Commands::Blank {
+            input,
+            before,
+            after,
+            odd_chapters,
+            size,
+            output,
+        } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "blank"));
+            insert_blank_pages(
+                &input,
+                &output,
+                before.as_deref(),
+                after.as_deref(),
+                odd_chapters.as_deref(),
+                size.as_deref(),
+                password.as_deref(),
+            )?
+        }
+        Commands::Crop {
+            input,
+            pages,
+            boxes,
+            rect,
+            margins,
+            size,
+            output,
+        } => {
+            let geometry = if let Some(rect) = rect {
+                match parse_lengths(&rect)?[..] {
+                    [x0, y0, x1, y1] => CropGeometry::Rect([x0, y0, x1, y1]),
+                    _ => bail!("Invalid rectangle: '{}' (expected X0,Y0,X1,Y1)", rect),
+                }
+            } else if let Some(margins) = margins {
+                CropGeometry::Margins(parse_margins(&margins)?)
+            } else {
+                CropGeometry::Size(parse_page_size(size.as_deref().unwrap_or_default())?)
+            };
+            let output = output.unwrap_or_else(|| default_output(&input, "cropped"));
+            crop_pdf(
+                &input,
+                &output,
+                pages.as_deref(),
+                &boxes,
+                &geometry,
+                password.as_deref(),
+            )?
+        }
+        Commands::Resize {
+            input,
+            pages,
+            size,
+            mode,
+            keep_orientation,
+            output,
+        } => {
+            let size = parse_page_size(&size)?;
+            let mode = parse_resize_mode(&mode)?;
+            let output = output.unwrap_or_else(|| default_output(&input, "resized"));
+            resize_pdf(
+                &input,
+                &output,
+                pages.as_deref(),
+                size,
+                mode,
+                keep_orientation,
+                password.as_deref(),
+            )?
+        }
+        Commands::Nup {
+            input,
+            pages,
+            grid,
+            sheet,
+            margin,
+            gutter,
+            order,
+            frame,
+            output,
+        } => {
+            let layout = NupLayout {
+                grid: parse_grid(&grid)?,
+                sheet: sheet.as_deref().map(parse_page_size).transpose()?,
+                margin: parse_length(&margin)?,
+                gutter: parse_length(&gutter)?,
+                order: order.to_ascii_lowercase(),
+                frame: frame.as_deref().map(parse_length).transpose()?,
+            };
+            let output = output.unwrap_or_else(|| default_output(&input, "nup"));
+            nup_pdf(
+                &input,
+                &output,
+                pages.as_deref(),
+                &layout,
+                password.as_deref(),
+            )?
+        }
+        Commands::Booklet {
+            input,
+            pages,
+            sheet,
+            signature,
+            creep,
+            rtl,
+            output,
+        } => {
+            let layout = BookletLayout {
+                sheet: sheet.as_deref().map(parse_page_size).transpose()?,
+                signature,
+                creep: parse_length(&creep)?,
+                right_to_left: rtl,
+            };
+            let output = output.unwrap_or_else(|| default_output(&input, "booklet"));
+            booklet_pdf(
+                &input,
+                &output,
+                pages.as_deref(),
+                &layout,
+                password.as_deref(),
+            )?
+        }
+        Commands::Tile {
+            input,
+            pages,
+            sheet,
+            margin,
+            overlap,
+            scale,
+            marks,
+            output,
+        } => {
+            let layout = TileLayout {
+                sheet: parse_page_size(&sheet)?,
+                margin: parse_length(&margin)?,
+                overlap: parse_length(&overlap)?,
+                scale,
+                marks,
+            };
+            let output = output.unwrap_or_else(|| default_output(&input, "tiled"));
+            tile_pdf(
+                &input,
+                &output,
+                pages.as_deref(),
+                &layout,
+                password.as_deref(),
+            )?
+        }
+        Commands::Stamp {
+            input,
+            pages,
+            text,
+            image,
+            pdf,
+            underlay,
+            opacity,
+            rotate,
+            position,
+            margin,
+            font,
+            font_size,
+            color,
+            width,
+            output,
+        } => {
+            let source = if let Some(text) = text {
+                StampSource::Text(text)
+            } else if let Some(image) = image {
+                StampSource::Image(image)
+            } else {
+                let (path, page) = split_source_spec(pdf.as_deref().unwrap_or_default());
+                let page = match page {
+                    Some(page) => page
+                        .parse()
+                        .with_context(|| format!("Invalid stamp page: '{}'", page))?,
+                    None => 1,
+                };
+                StampSource::Pdf(path, page)
+            };
+            let options = StampOptions {
+                source,
+                underlay,
+                opacity,
+                rotation: rotate,
+                position: position.to_ascii_lowercase(),
+                margin: parse_length(&margin)?,
+                font: parse_standard_font(&font)?,
+                font_size,
+                color: parse_color(&color)?,
+                width: width.as_deref().map(parse_length).transpose()?,
+            };
+            let output = output.unwrap_or_else(|| default_output(&input, "stamped"));
+            stamp_pdf(
+                &input,
+                &output,
+                pages.as_deref(),
+                &options,
+                password.as_deref(),
+            )?
+        }
+        Commands::Overlay {
+            input,
+            background,
+            foreground,
+            repeat,
+            cycle,
+            map,
+            output,
+        } => {
+            let mapping = match map {
+                Some(spec) => OverlayMapping::Explicit(spec),
+                None if repeat => OverlayMapping::Repeat,
+                None if cycle => OverlayMapping::Cycle,
+                None => OverlayMapping::Match,
+            };
+            let output = output.unwrap_or_else(|| default_output(&input, "overlaid"));
+            overlay_pdf(
+                &input,
+                &output,
+                background.as_deref(),
+                foreground.as_deref(),
+                &mapping,
+                password.as_deref(),
+            )?
+        }
+        Commands::Number {
+            input,
+            header,
+            footer,
+            align,
+            margin,
+            font,
+            font_size,
+            color,
+            start,
+            skip,
+            output,
+        } => {
+            let footer = match (&header, footer) {
+                (None, None) => Some("{page}".to_string()),
+                (_, footer) => footer,
+            };
+            let options = NumberOptions {
+                header,
+                footer,
+                align: align.to_ascii_lowercase(),
+                start,
+                skip,
+                font: parse_standard_font(&font)?,
+                font_size,
+                color: parse_color(&color)?,
+                margin: parse_length(&margin)?,
+            };
+            let output = output.unwrap_or_else(|| default_output(&input, "numbered"));
+            number_pdf(&input, &output, &options, password.as_deref())?
+        }
+        Commands::Bates {
+            inputs,
+            prefix,
+            suffix,
+            digits,
+            start,
+            continue_from,
+            position,
+            margin,
+            font,
+            font_size,
+            color,
+            log,
+            output,
+        } => {
+            let pdf_files = collect_input_pdfs(&inputs, cli.recursive)?;
+            let start = match &continue_from {
+                Some(log) => next_bates_number(log, &prefix, &suffix)?,
+                None => start,
+            };
+            let options = BatesOptions {
+                prefix,
+                suffix,
+                digits,
+                start,
+                position: position.to_ascii_lowercase(),
+                font: parse_standard_font(&font)?,
+                font_size,
+                color: parse_color(&color)?,
+                margin: parse_length(&margin)?,
+            };
+            bates_pdfs(
+                &pdf_files,
+                &inputs,
+                &output,
+                log.as_deref(),
+                &options,
+                password.as_deref(),
+            )?
+        }
+        Commands::Outline {
+            input,
+            import,
+            append,
+            export,
+            json,
+            output,
+        } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "outline"));
+            edit_outline(
+                &input,
+                &output,
+                import.as_deref(),
+                append,
+                export.as_deref(),
+                json,
+                password.as_deref(),
+            )?
+        }
+        Commands::Labels {
+            input,
+            spec,
+            clear,
+            output,
+        } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "labeled"));
+            edit_labels(&input, &output, spec.as_deref(), clear, password.as_deref())?
+        }
+        Commands::Decrypt { input, output } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "decrypted"));
+            decrypt_pdf(&input, &output, password.as_deref())?
+        }
+        Commands::Encrypt {
+            input,
+            settings,
+            output,
+        } => {
+            let output = output.unwrap_or_else(|| default_output(&input, "encrypted"));
+            encrypt_pdf(&input, &output, password.as_deref(), &settings)?
+        }
+    }
+    Ok(())
+}
+
+fn show_pdf_info(path: &Path, password: Option<&str>) -> Result<usize> {
+    let doc = load_document(path, password)?;
+
+    if doc.is_encrypted() {
+        println!("📄 {}", path.display());
+        println!("   Version: {}", doc.version);
+        if let Some(dict) = encryption_dict(&doc) {
+            print_encryption(&dict);
+        }
+        println!("   ⚠️ Password required to read this file (use --password)");
+        return Ok(0);
+    }
+
+    let page_count = doc.get_pages().len();
+
+    println!("📄 {}", path.display());
+    println!("   Pages: {}", page_count);
+    println!("   Version: {}", doc.version);
+
+    for (key, value) in info_entries(&doc) {
+        if matches!(key.as_str(), "Title" | "Author" | "Subject") {
+            println!("   {}: {}", key, value);
+        }
+    }
+
+    let pages = doc.get_pages();
+    let page_nums: Vec<u32> = pages.keys().copied().collect();
+    if !page_nums.is_empty() {
+        let mut sorted = page_nums.clone();
+        sorted.sort_unstable();
+        if sorted.len() <= 10 {
+            println!("   Page numbers: {:?}", sorted);
+        } else {
+            println!(
+                "   Page numbers: {} to {}",
+                sorted[0],
+                sorted[sorted.len() - 1]
+            );
+        }
+    }
+
+    if let Some(dict) = encryption_dict(&doc) {
+        print_encryption(&dict);
+    }
+
+    Ok(page_count)
+}
+
+fn resolve_password(args: &PasswordArgs) -> Result<Option<String>> {
+    if let Some(password) = &args.password {
+        return Ok(Some(password.clone()));
+    }
+    if let Some(path) = &args.password_file {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read password file: {}", path.display()))?;
+        let password = contents.lines().next().unwrap_or("");
+        return Ok(Some(password.to_string()));
+    }
+    Ok(std::env::var("PDFER_PASSWORD").ok())
+}
+
+/// Loads `path`, decrypting it when `password` (or the empty user password)
+/// opens it. A document that stays locked is returned still encrypted.
+fn load_document(path: &Path, password: Option<&str>) -> Result<Document> {
+    let doc =
+        Document::load(path).with_context(|| format!("Failed to load PDF: {}", path.display()))?;
+    let Some(password) = password.filter(|_| doc.is_encrypted()) else {
+        return Ok(doc);
+    };
+    if doc.authenticate_password(password).is_err() {
+        bail!("Incorrect password for {}", path.display());
+    }
+
+    // lopdf derives the key as if any password were the user password,
+    // which only holds for owner passwords from revision 5 on.
+    let password = user_password_from_owner(&doc, password).unwrap_or_else(|| password.to_string());
+    Document::load_with_password(path, &password)
+        .with_context(|| format!("Failed to decrypt: {}", path.display()))
+}
+
+/// Padding for passwords of the revision 2-4 security handlers.
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// The user password of a revision 2-4 file, recovered from its /O entry
+/// (Algorithm 7 of ISO 32000-2), when `password` opens the locked `doc` as
+/// its owner password only. `None` otherwise.
+fn user_password_from_owner(doc: &Document, password: &str) -> Option<String> {
+    let dict = doc.get_encrypted().ok()?;
+    let revision = dict.get(b"R").and_then(Object::as_i64).ok()?;
+    if revision > 4
+        || doc.authenticate_user_password(password).is_ok()
+        || doc.authenticate_owner_password(password).is_err()
+    {
+        return None;
+    }
+
+    let password = lopdf::encryption::PasswordAlgorithm::try_from(doc)
+        .ok()?
+        .sanitize_password(password)
+        .ok()?;
+    let len = password.len().min(32);
+    let mut hash = Md5::new()
+        .chain_update(&password[..len])
+        .chain_update(&PASSWORD_PADDING[..32 - len])
+        .finalize();
+    let (key_len, rounds) = if revision >= 3 {
+        for _ in 0..50 {
+            hash = Md5::digest(hash);
+        }
+        let length = dict.get(b"Length").and_then(Object::as_i64).unwrap_or(40);
+        ((length / 8).clamp(5, 16) as usize, 19)
+    } else {
+        (5, 0)
+    };
+
+    let owner_value = dict.get(b"O").and_then(Object::as_str).ok()?;
+    if owner_value.len() != 32 {
+        return None;
+    }
+    let mut user_password = owner_value.to_vec();
+    for round in (0..=rounds).rev() {
+        let key: Vec<u8> = hash[..key_len].iter().map(|byte| byte ^ round).collect();
+        user_password = rc4(&key, &user_password);
+    }
+    let len = (0..=32)
+        .find(|&len| user_password[len..] == PASSWORD_PADDING[..32 - len])
+        .unwrap_or(32);
+    user_password.truncate(len);
+    decode_text_string(&Object::string_literal(user_password)).ok()
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: [u8; 256] = std::array::from_fn(|i| i as u8);
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            byte ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// Like `load_document`, but fails unless the contents are readable.
+fn load_pdf(path: &Path, password: Option<&str>) -> Result<Document> {
+    let mut doc = load_document(path, password)?;
+    if doc.is_encrypted() {
+        bail!(
+            "{} is encrypted. Provide the password with --password, --password-file or PDFER_PASSWORD",
+            path.display()
+        );
+    }
+    // lopdf drops the /Encrypt dictionary once it has decrypted the objects
+    // but leaves the trailer pointing at it.
+    doc.trailer.remove(b"Encrypt");
+    Ok(doc)
+}
+
+fn encryption_dict(doc: &Document) -> Option<Dictionary> {
+    if let Ok(dict) = doc.get_encrypted() {
+        return Some(dict.clone());
+    }
+    doc.encryption_state
+        .as_ref()
+        .and_then(|state| state.encode().ok())
+}
+
+const PERMISSION_FLAGS: [(&str, Permissions); 7] = [
+    ("print", Permissions::PRINTABLE),
+    ("print-high", Permissions::PRINTABLE_IN_HIGH_QUALITY),
+    ("copy", Permissions::COPYABLE),
+    ("modify", Permissions::MODIFIABLE),
+    ("annotate", Permissions::ANNOTABLE),
+    ("fill-forms", Permissions::FILLABLE),
+    ("assemble", Permissions::ASSEMBLABLE),
+];
+
+fn encryption_method(dict: &Dictionary) -> String {
+    let int = |key: &[u8]| dict.get(key).and_then(Object::as_i64).ok();
+    let method = match int(b"V").unwrap_or(0) {
+        1 => "RC4 40-bit".to_string(),
+        2 | 3 => format!("RC4 {}-bit", int(b"Length").unwrap_or(40)),
+        4 => {
+            let filter = dict
+                .get(b"StmF")
+                .and_then(Object::as_name)
+                .unwrap_or(b"Identity");
+            let method = dict
+                .get(b"CF")
+                .and_then(Object::as_dict)
+                .and_then(|filters| filters.get(filter))
+                .and_then(Object::as_dict)
+                .and_then(|filter| filter.get(b"CFM"))
+                .and_then(Object::as_name)
+                .unwrap_or(b"None");
+            match method {
+                b"AESV2" => "AES-128".to_string(),
+                b"V2" => "RC4 128-bit".to_string(),
+                _ => "none (identity filter)".to_string(),
+            }
+        }
+        5 => "AES-256".to_string(),
+        version => format!("unknown handler (V {})", version),
+    };
+    match int(b"R") {
+        Some(revision) => format!("{} (revision {})", method, revision),
+        None => method,
+    }
+}
+
+fn print_encryption(dict: &Dictionary) {
+    println!("   Encryption: {}", encryption_method(dict));
+    let Ok(bits) = dict.get(b"P").and_then(Object::as_i64) else {
+        return;
+    };
+    let permissions = Permissions::from_bits_truncate(bits as u32 as u64);
+    let allowed: Vec<&str> = PERMISSION_FLAGS
+        .iter()
+        .filter(|(_, flag)| permissions.contains(*flag))
+        .map(|(name, _)| *name)
+        .collect();
+    if allowed.is_empty() {
+        println!("   Permissions: none");
+    } else {
+        println!("   Permissions: {}", allowed.join(", "));
+    }
+}
+
+fn parse_permissions(spec: &str) -> Result<Permissions> {
+    // Accessibility extraction is always granted; PDF 2.0 requires the bit.
+    let mut permissions = Permissions::COPYABLE_FOR_ACCESSIBILITY;
+    for name in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        match name.to_ascii_lowercase().as_str() {
+            "all" => permissions |= Permissions::all(),
+            "none" => {}
+            name => {
+                let Some((_, flag)) = PERMISSION_FLAGS.iter().find(|(flag, _)| *flag == name)
+                else {
+                    let names: Vec<&str> = PERMISSION_FLAGS.iter().map(|(name, _)| *name).collect();
+                    bail!(
+                        "Unknown permission '{}' (expected all, none, {})",
+                        name,
+                        names.join(", ")
+                    );
+                };
+                permissions |= *flag;
+            }
+        }
+    }
+    if permissions.contains(Permissions::PRINTABLE_IN_HIGH_QUALITY) {
+        permissions |= Permissions::PRINTABLE;
+    }
+    Ok(permissions)
+}
+
+/// Checks the encryption settings up front so that a typo fails before
+/// any output is written.
+fn validate_encryption(args: &EncryptArgs) -> Result<()> {
+    let permissions = parse_permissions(&args.allow)?;
+    if args.user_password.as_deref().unwrap_or("").is_empty()
+        && permissions.contains(Permissions::all())
+    {
+        bail!("Nothing to protect: set --user-password or restrict permissions with --allow");
+    }
+    if args.owner_password.is_none() && !permissions.contains(Permissions::all()) {
+        bail!("--allow restricts permissions; set --owner-password so they can be lifted later");
+    }
+    Ok(())
+}
+
+/// Returns the settings to encrypt merge/split outputs with, or `None`
+/// when --encrypt was not given. An explicit `pdf_version` too old for the
+/// cipher is rejected rather than silently raised.
+fn output_encryption<'a>(
+    args: &'a OutputEncryptionArgs,
+    pdf_version: Option<&str>,
+) -> Result<Option<&'a EncryptArgs>> {
+    let settings = &args.settings;
+    if !args.encrypt {
+        if settings.user_password.is_some()
+            || settings.owner_password.is_some()
+            || settings.allow != "all"
+            || settings.aes128
+        {
+            bail!("--user-password, --owner-password, --allow and --aes128 require --encrypt");
+        }
+        return Ok(None);
+    }
+    validate_encryption(settings)?;
+    if let Some(requested) = pdf_version {
+        let (needed, cipher) = if settings.aes128 {
+            ((1, 6), "AES-128")
+        } else {
+            ((1, 7), "AES-256")
+        };
+        if parse_pdf_version(requested)? < needed {
+            bail!(
+                "--pdf-version {} is too old for {} encryption (requires {}.{} or later)",
+                requested,
+                cipher,
+                needed.0,
+                needed.1
+            );
+        }
+    }
+    Ok(Some(settings))
+}
+
+/// Gives the document a fresh file identifier, keeping the permanent
+/// first half when one exists. The standard security handler mixes the
+/// identifier into the encryption key.
+fn set_file_id(doc: &mut Document) {
+    let changing: [u8; 16] = rand::random();
+    let permanent = doc
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|ids| ids.first())
+        .and_then(|id| id.as_str().ok())
+        .filter(|id| !id.is_empty())
+        .map(<[u8]>::to_vec)
+        .unwrap_or_else(|| changing.to_vec());
+    doc.trailer.set(
+        "ID",
+        Object::Array(vec![
+            Object::String(permanent, StringFormat::Hexadecimal),
+            Object::String(changing.to_vec(), StringFormat::Hexadecimal),
+        ]),
+    );
+}
+
+/// Raises the document version to one that supports the cipher: 1.6 for
+/// AES-128, and 2.0 or 1.7 with Adobe extension level 8 for AES-256.
+fn require_encryption_version(doc: &mut Document, aes128: bool) -> Result<()> {
+    let version = document_version(doc);
+    if aes128 {
+        if version < (1, 6) {
+            doc.version = "1.6".to_string();
+        }
+        return Ok(());
+    }
+    if version >= (2, 0) {
+        return Ok(());
+    }
+    if version < (1, 7) {
+        doc.version = "1.7".to_string();
+    }
+
+    let mut extensions = doc
+        .catalog()?
+        .get(b"Extensions")
+        .ok()
+        .and_then(|extensions| doc.dereference(extensions).ok())
+        .and_then(|(_, extensions)| extensions.as_dict().ok().cloned())
+        .unwrap_or_default();
+    extensions.set(
+        "ADBE",
+        dictionary! {
+            "BaseVersion" => Object::Name(b"1.7".to_vec()),
+            "ExtensionLevel" => 8,
+        },
+    );
+    doc.catalog_mut()?.set("Extensions", extensions);
+    Ok(())
+}
+
+/// Encrypts every string and stream in `doc` with the standard security
+/// handler and returns a description of the cipher used.
+fn encrypt_document(doc: &mut Document, args: &EncryptArgs) -> Result<String> {
+    let permissions = parse_permissions(&args.allow)?;
+    let user_password = args.user_password.as_deref().unwrap_or("");
+    let owner_password = match &args.owner_password {
+        Some(password) => password.clone(),
+        // Only reachable without restrictions (see validate_encryption),
+        // where nobody needs the owner password.
+        None => rand::random::<[u8; 16]>()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    };
+
+    require_encryption_version(doc, args.aes128)?;
+    set_file_id(doc);
+
+    let file_encryption_key: [u8; 32] = rand::random();
+    let version = if args.aes128 {
+        let filter: Arc<dyn CryptFilter> = Arc::new(Aes128CryptFilter);
+        EncryptionVersion::V4 {
+            document: doc,
+            encrypt_metadata: true,
+            crypt_filters: BTreeMap::from([(b"StdCF".to_vec(), filter)]),
+            stream_filter: b"StdCF".to_vec(),
+            string_filter: b"StdCF".to_vec(),
+            owner_password: &owner_password,
+            user_password,
+            permissions,
+        }
+    } else {
+        let filter: Arc<dyn CryptFilter> = Arc::new(Aes256CryptFilter);
+        EncryptionVersion::V5 {
+            encrypt_metadata: true,
+            crypt_filters: BTreeMap::from([(b"StdCF".to_vec(), filter)]),
+            file_encryption_key: &file_encryption_key,
+            stream_filter: b"StdCF".to_vec(),
+            string_filter: b"StdCF".to_vec(),
+            owner_password: &owner_password,
+            user_password,
+            permissions,
+        }
+    };
+    let state = EncryptionState::try_from(version).context("Failed to set up encryption")?;
+    doc.encrypt(&state).context("Failed to encrypt document")?;
+
+    Ok(encryption_dict(doc)
+        .map(|dict| encryption_method(&dict))
+        .unwrap_or_default())
+}
+
+const INFO_KEYS: [&str; 8] = [
+    "Title",
+    "Author",
+    "Subject",
+    "Keywords",
+    "Creator",
+    "Producer",
+    "CreationDate",
+    "ModDate",
+];
+
+fn info_dict(doc: &Document) -> Option<&Dictionary> {
+    match doc.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => doc.get_dictionary(*id).ok(),
+        Object::Dictionary(dict) => Some(dict),
+        _ => None,
+    }
+}
+
+fn info_dict_mut(doc: &mut Document) -> Result<&mut Dictionary> {
+    let info_id = match doc.trailer.get(b"Info") {
+        Ok(Object::Reference(id)) if doc.get_dictionary(*id).is_ok() => *id,
+        Ok(Object::Dictionary(dict)) => {
+            let dict = dict.clone();
+            doc.add_object(dict)
+        }
+        _ => doc.add_object(Dictionary::new()),
+    };
+    doc.trailer.set("Info", Object::Reference(info_id));
+    Ok(doc.get_dictionary_mut(info_id)?)
+}
+
+fn info_entries(doc: &Document) -> Vec<(String, String)> {
+    let Some(info) = info_dict(doc) else {
+        return Vec::new();
+    };
+
+    let mut entries = Vec::new();
+    for (key, value) in info.iter() {
+        let value = match doc.dereference(value) {
+            Ok((_, value @ Object::String(..))) => decode_text_string(value)
+                .unwrap_or_else(|_| String::from_utf8_lossy(value.as_str().unwrap()).to_string()),
+            Ok((_, Object::Name(name))) => String::from_utf8_lossy(name).to_string(),
+            _ => continue,
+        };
+        entries.push((String::from_utf8_lossy(key).to_string(), value));
+    }
+
+    entries.sort_by_key(|(key, _)| {
+        INFO_KEYS
+            .iter()
+            .position(|k| k == key)
+            .unwrap_or(INFO_KEYS.len())
+    });
+    entries
+}
+
+fn canonical_info_key(key: &str) -> Result<String> {
+    let key = key.trim();
+    if key.is_empty()
+        || key
+            .chars()
+            .any(|c| c.is_whitespace() || "/()<>[]{}%#".contains(c))
+    {
+        bail!("Invalid metadata key: '{}'", key);
+    }
+
+    Ok(INFO_KEYS
+        .iter()
+        .find(|k| k.eq_ignore_ascii_case(key))
+        .map(|k| k.to_string())
+        .unwrap_or_else(|| key.to_string()))
+}
+
+fn set_info_entry(doc: &mut Document, key: &str, value: &str) -> Result<()> {
+    let value = if value.eq_ignore_ascii_case("now") && key.ends_with("Date") {
+        Object::from(Local::now())
+    } else {
+        text_string(value)
+    };
+    info_dict_mut(doc)?.set(key.as_bytes().to_vec(), value);
+    Ok(())
+}
+
+fn pdf_date_to_iso(date: &str) -> Option<String> {
+    let date = date.strip_prefix("D:").unwrap_or(date);
+    let digits: String = date.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+
+    let field = |start: usize, default: &str| {
+        digits
+            .get(start..start + 2)
+            .map(str::to_string)
+            .unwrap_or_else(|| default.to_string())
+    };
+    let mut iso = format!(
+        "{}-{}-{}T{}:{}:{}",
+        &digits[..4],
+        field(4, "01"),
+        field(6, "01"),
+        field(8, "00"),
+        field(10, "00"),
+        field(12, "00")
+    );
+
+    let offset = &date[digits.len()..];
+    match offset.chars().next() {
+        Some('Z') | None => iso.push('Z'),
+        Some(sign @ ('+' | '-')) => {
+            let parts: Vec<&str> = offset[1..].split('\'').filter(|p| !p.is_empty()).collect();
+            let hours = parts.first().copied().unwrap_or("00");
+            let minutes = parts.get(1).copied().unwrap_or("00");
+            iso.push_str(&format!("{}{}:{}", sign, hours, minutes));
+        }
+        _ => return None,
+    }
+    Some(iso)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const XMP_RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// Properties mirrored from /Info, by namespace URI. Only these are
+/// replaced in an existing packet; other schemas (PDF/A identification,
+/// xmpMM, custom namespaces) are left as they are.
+const XMP_INFO_PROPERTIES: [(&str, &[&str]); 3] = [
+    (
+        "http://purl.org/dc/elements/1.1/",
+        &["title", "creator", "description"],
+    ),
+    ("http://ns.adobe.com/pdf/1.3/", &["Keywords", "Producer"]),
+    (
+        "http://ns.adobe.com/xap/1.0/",
+        &["CreatorTool", "CreateDate", "ModifyDate", "MetadataDate"],
+    ),
+];
+
+/// An `rdf:Description` holding the dc, pdf and xmp property elements.
+fn xmp_description(rdf: &str, dc: &str, pdf: &str, xmp: &str) -> String {
+    format!(
+        "<{rdf}:Description {rdf}:about=\"\"\n\
+         \x20   xmlns:dc=\"{}\"\n\
+         \x20   xmlns:pdf=\"{}\"\n\
+         \x20   xmlns:xmp=\"{}\">\n\
+         {}{}{}\
+         </{rdf}:Description>\n",
+        XMP_INFO_PROPERTIES[0].0, XMP_INFO_PROPERTIES[1].0, XMP_INFO_PROPERTIES[2].0, dc, pdf, xmp
+    )
+}
+
+/// Removes the /Info-derived properties from an existing XMP `packet`,
+/// under whatever prefixes it binds their namespaces to, and adds the new
+/// ones in a description of their own. `None` if the packet has no RDF
+/// body to update.
+fn update_xmp_packet(packet: &str, dc: &str, pdf: &str, xmp: &str) -> Option<String> {
+    let prefixes = |uri: &str| -> Vec<String> {
+        let binding = format!(r#"xmlns:([\w.-]+)\s*=\s*["']{}["']"#, regex::escape(uri));
+        RegexBuilder::new(&binding)
+            .build()
+            .map(|binding| {
+                binding
+                    .captures_iter(packet)
+                    .map(|captures| captures[1].to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let rdf = prefixes(XMP_RDF_NAMESPACE).into_iter().next()?;
+
+    let mut updated = packet.to_string();
+    for (uri, names) in XMP_INFO_PROPERTIES {
+        for prefix in prefixes(uri) {
+            for name in names {
+                let qualified = regex::escape(&format!("{}:{}", prefix, name));
+                let element = format!(
+                    r"(?s)[ \t]*<{0}(?:\s[^>]*?)?(?:/>|>.*?</{0}\s*>)\r?\n?",
+                    qualified
+                );
+                let attribute = format!(r#"\s+{}\s*=\s*(?:"[^"]*"|'[^']*')"#, qualified);
+                for pattern in [element, attribute] {
+                    let pattern = RegexBuilder::new(&pattern).build().ok()?;
+                    updated = pattern.replace_all(&updated, "").into_owned();
+                }
+            }
+        }
+    }
+
+    let end = updated.rfind(&format!("</{}:RDF>", rdf))?;
+    if !(dc.is_empty() && pdf.is_empty() && xmp.is_empty()) {
+        updated.insert_str(end, &xmp_description(&rdf, dc, pdf, xmp));
+    }
+    Some(updated)
+}
+
+/// Brings the catalog's XMP packet in line with the current /Info entries.
+fn sync_xmp_metadata(doc: &mut Document) -> Result<()> {
+    let entries: HashMap<String, String> = info_entries(doc).into_iter().collect();
+    let get = |key: &str| entries.get(key).map(|v| xml_escape(v));
+    let date = |key: &str| entries.get(key).and_then(|v| pdf_date_to_iso(v));
+
+    let mut dc = String::new();
+    if let Some(title) = get("Title") {
+        dc.push_str(&format!(
+            "   <dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>\n",
+            title
+        ));
+    }
+    if let Some(author) = get("Author") {
+        dc.push_str(&format!(
+            "   <dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n",
+            author
+        ));
+    }
+    if let Some(subject) = get("Subject") {
+        dc.push_str(&format!(
+            "   <dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>\n",
+            subject
+        ));
+    }
+
+    let mut pdf = String::new();
+    if let Some(keywords) = get("Keywords") {
+        pdf.push_str(&format!("   <pdf:Keywords>{}</pdf:Keywords>\n", keywords));
+    }
+    if let Some(producer) = get("Producer") {
+        pdf.push_str(&format!("   <pdf:Producer>{}</pdf:Producer>\n", producer));
+    }
+
+    let mut xmp = String::new();
+    if let Some(creator) = get("Creator") {
+        xmp.push_str(&format!(
+            "   <xmp:CreatorTool>{}</xmp:CreatorTool>\n",
+            creator
+        ));
+    }
+    if let Some(created) = date("CreationDate") {
+        xmp.push_str(&format!(
+            "   <xmp:CreateDate>{}</xmp:CreateDate>\n",
+            created
+        ));
+    }
+    if let Some(modified) = date("ModDate") {
+        xmp.push_str(&format!(
+            "   <xmp:ModifyDate>{}</xmp:ModifyDate>\n",
+            modified
+        ));
+        xmp.push_str(&format!(
+            "   <xmp:MetadataDate>{}</xmp:MetadataDate>\n",
+            modified
+        ));
+    }
+
+    let existing = match doc.catalog()?.get(b"Metadata") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+    let existing_stream = existing
+        .and_then(|id| doc.get_object(id).ok())
+        .and_then(|object| object.as_stream().ok());
+    let packet = existing_stream
+        .and_then(|stream| stream.get_plain_content().ok())
+        .and_then(|content| String::from_utf8(content).ok())
+        .and_then(|packet| update_xmp_packet(&packet, &dc, &pdf, &xmp));
+    let packet = match packet {
+        Some(packet) => packet,
+        None => format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+             <rdf:RDF xmlns:rdf=\"{}\">\n\
+             {}\
+             </rdf:RDF>\n\
+             </x:xmpmeta>\n\
+             <?xpacket end=\"w\"?>",
+            XMP_RDF_NAMESPACE,
+            xmp_description("rdf", &dc, &pdf, &xmp)
+        ),
+    };
+
+    let mut dict = match existing_stream {
+        Some(stream) => stream.dict.clone(),
+        None => Dictionary::new(),
+    };
+    dict.remove(b"Filter");
+    dict.remove(b"DecodeParms");
+    dict.set("Type", Object::Name(b"Metadata".to_vec()));
+    dict.set("Subtype", Object::Name(b"XML".to_vec()));
+    let stream = Stream::new(dict, packet.into_bytes()).with_compression(false);
+
+    let metadata_id = match existing {
+        Some(id) => {
+            doc.objects.insert(id, Object::Stream(stream));
+            id
+        }
+        None => doc.add_object(stream),
+    };
+    doc.catalog_mut()?
+        .set("Metadata", Object::Reference(metadata_id));
+    Ok(())
+}
+
+fn print_metadata(doc: &Document, path: &Path) {
+    println!("📄 {}", path.display());
+
+    let entries = info_entries(doc);
+    if entries.is_empty() {
+        println!("   (no /Info metadata)");
+    }
+    for (key, value) in &entries {
+        println!("   {}: {}", key, value);
+    }
+
+    let xmp_len = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Metadata").ok())
+        .and_then(|obj| doc.dereference(obj).ok())
+        .and_then(|(_, obj)| obj.as_stream().ok())
+        .map(|stream| stream.content.len());
+    match xmp_len {
+        Some(len) => println!("   XMP: present ({} bytes)", len),
+        None => println!("   XMP: none"),
+    }
+}
+
+fn metadata_to_json(doc: &Document) -> String {
+    let map: serde_json::Map<String, serde_json::Value> = info_entries(doc)
+        .into_iter()
+        .map(|(key, value)| (key, serde_json::Value::String(value)))
+        .collect();
+    serde_json::to_string_pretty(&serde_json::Value::Object(map)).unwrap_or_default()
+}
+
+fn import_metadata_json(doc: &mut Document, path: &Path) -> Result<Vec<String>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read metadata file: {}", path.display()))?;
+    let json: serde_json::Value = serde_json::from_str(&data)
+        .with_context(|| format!("Invalid JSON in {}", path.display()))?;
+    let Some(map) = json.as_object() else {
+        bail!(
+            "Metadata file must contain a JSON object: {}",
+            path.display()
+        );
+    };
+
+    let info = info_dict_mut(doc)?;
+    *info = Dictionary::new();
+    let mut keys = Vec::new();
+    for (key, value) in map {
+        let key = canonical_info_key(key)?;
+        match value {
+            serde_json::Value::Null => {}
+            serde_json::Value::String(value) => set_info_entry(doc, &key, value)?,
+            other => set_info_entry(doc, &key, &other.to_string())?,
+        }
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+fn edit_metadata(
+    input: &Path,
+    output: &Path,
+    set: &[String],
+    delete: &[String],
+    import: Option<&Path>,
+    export: Option<&Path>,
+    password: Option<&str>,
+) -> Result<()> {
+    let mut doc = load_pdf(input, password)?;
+
+    let mut updates = Vec::new();
+    for entry in set {
+        let Some((key, value)) = entry.split_once('=') else {
+            bail!("Invalid --set value: '{}' (expected KEY=VALUE)", entry);
+        };
+        updates.push((canonical_info_key(key)?, value.to_string()));
+    }
+    let mut deletions = delete
+        .iter()
+        .map(|key| canonical_info_key(key))
+        .collect::<Result<Vec<_>>>()?;
+    deletions.retain(|key| info_dict(&doc).is_some_and(|info| info.has(key.as_bytes())));
+
+    let modified = import.is_some() || !updates.is_empty() || !deletions.is_empty();
+    if !modified && !delete.is_empty() {
+        println!(
+            "⚠️ None of the keys to delete are set in {}; nothing changed",
+            input.display()
+        );
+    } else if modified {
+        let Some(current_output) = prepare_output_file(output)? else {
+            return Ok(());
+        };
+
+        let mut explicit_keys = Vec::new();
+        if let Some(import) = import {
+            explicit_keys.extend(import_metadata_json(&mut doc, import)?);
+        }
+        for (key, value) in &updates {
+            set_info_entry(&mut doc, key, value)?;
+            explicit_keys.push(key.clone());
+        }
+        for key in &deletions {
+            info_dict_mut(&mut doc)?.remove(key.as_bytes());
+            explicit_keys.push(key.clone());
+        }
+        if !explicit_keys.iter().any(|key| key == "ModDate") {
+            set_info_entry(&mut doc, "ModDate", "now")?;
+        }
+        sync_xmp_metadata(&mut doc)?;
+
+        doc.save(&current_output)
+            .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+        print_metadata(&doc, &current_output);
+        println!("✓ Metadata saved: {}", current_output.display());
+    } else if export.is_none() {
+        print_metadata(&doc, input);
+    }
+
+    if let Some(export) = export {
+        let json = metadata_to_json(&doc);
+        if export == Path::new("-") {
+            println!("{}", json);
+        } else {
+            let Some(export_path) = resolve_output_conflict(export, false)? else {
+                return Ok(());
+            };
+            std::fs::write(&export_path, json + "\n")
+                .with_context(|| format!("Failed to write: {}", export_path.display()))?;
+            println!("✓ Metadata exported: {}", export_path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Fills the /Info of a newly built document from `inherited` entries,
+/// applies command-line overrides and stamps Producer and dates.
+fn apply_output_metadata(
+    doc: &mut Document,
+    inherited: &[(String, String)],
+    metadata: &MetadataArgs,
+) -> Result<()> {
+    for (key, value) in inherited {
+        if !matches!(key.as_str(), "Producer" | "CreationDate" | "ModDate") {
+            set_info_entry(doc, key, value)?;
+        }
+    }
+
+    let overrides = [
+        ("Title", &metadata.title),
+        ("Author", &metadata.author),
+        ("Subject", &metadata.subject),
+    ];
+    for (key, value) in overrides {
+        if let Some(value) = value {
+            set_info_entry(doc, key, value)?;
+        }
+    }
+
+    set_info_entry(
+        doc,
+        "Producer",
+        concat!("pdfer ", env!("CARGO_PKG_VERSION")),
+    )?;
+    set_info_entry(doc, "CreationDate", "now")?;
+    set_info_entry(doc, "ModDate", "now")?;
+    sync_xmp_metadata(doc)
+}
+
+/// Drops every object that can no longer be reached from the trailer and
+/// returns how many were removed. References to missing objects are
+/// skipped, as readers treat them as null in damaged files.
+fn prune_unreachable_objects(doc: &mut Document) -> usize {
+    let mut reachable = HashSet::new();
+    let mut pending = vec![Object::Dictionary(doc.trailer.clone())];
+    while let Some(object) = pending.pop() {
+        match object {
+            Object::Reference(id) => {
+                if let Ok(target) = doc.get_object(id)
+                    && reachable.insert(id)
+                {
+                    pending.push(target.clone());
+                }
+            }
+            Object::Array(items) => pending.extend(items),
+            Object::Dictionary(dict) => pending.extend(dict.into_iter().map(|(_, val)| val)),
+            Object::Stream(stream) => pending.extend(stream.dict.into_iter().map(|(_, val)| val)),
+            _ => {}
+        }
+    }
+
+    let before = doc.objects.len();
+    doc.objects.retain(|id, _| reachable.contains(id));
+    before - doc.objects.len()
+}
+
+/// Classifies a dictionary that `sanitize` removes wherever it is referenced.
+fn active_content_category(dict: &Dictionary) -> Option<&'static str> {
+    let is_action = dict
+        .get(b"Type")
+        .and_then(Object::as_name)
+        .map_or(true, |t| t == b"Action");
+    match dict.get(b"S").and_then(Object::as_name) {
+        Ok(b"JavaScript") if is_action => return Some("JavaScript actions"),
+        Ok(b"SubmitForm") if is_action => return Some("form submit actions"),
+        _ => {}
+    }
+    match dict.get(b"Subtype").and_then(Object::as_name) {
+        Ok(b"FileAttachment") => Some("file attachment annotations"),
+        _ => None,
+    }
+}
+
+const PRIVATE_DATA_KEYS: [(&[u8], &str); 4] = [
+    (b"Metadata", "XMP metadata streams"),
+    (b"PieceInfo", "private application data (/PieceInfo)"),
+    (b"Thumb", "page thumbnails"),
+    (b"AF", "associated files"),
+];
+
+/// Walks `object` recursively, dropping private data keys, actions that
+/// run JavaScript or submit forms (including inside /AA and /Next chains)
+/// and file attachment annotations, whether inline or referenced through
+/// an id in `flagged`.
+fn strip_active_content(
+    object: &mut Object,
+    flagged: &HashMap<ObjectId, &'static str>,
+    removed: &mut BTreeMap<&'static str, usize>,
+) {
+    let category = |object: &Object| match object {
+        Object::Reference(id) => flagged.get(id).copied(),
+        Object::Dictionary(dict) => active_content_category(dict),
+        _ => None,
+    };
+
+    let dict = match object {
+        Object::Array(items) => {
+            items.retain(|item| match category(item) {
+                Some(category) => {
+                    *removed.entry(category).or_default() += 1;
+                    false
+                }
+                None => true,
+            });
+            for item in items {
+                strip_active_content(item, flagged, removed);
+            }
+            return;
+        }
+        Object::Dictionary(dict) => dict,
+        Object::Stream(stream) => &mut stream.dict,
+        _ => return,
+    };
+
+    for (key, category) in PRIVATE_DATA_KEYS {
+        if dict.remove(key).is_some() {
+            *removed.entry(category).or_default() += 1;
+        }
+    }
+    // Annotations are only dropped from arrays (/Annots); a popup's /Parent
+    // pointing at an attachment is left for pruning to sort out.
+    let actions: Vec<(Vec<u8>, &'static str)> = dict
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), category(value)?)))
+        .filter(|(_, category)| *category != "file attachment annotations")
+        .collect();
+    for (key, category) in actions {
+        dict.remove(&key);
+        *removed.entry(category).or_default() += 1;
+    }
+    for (_, value) in dict.iter_mut() {
+        strip_active_content(value, flagged, removed);
+    }
+    if matches!(dict.get(b"Next"), Ok(Object::Array(next)) if next.is_empty()) {
+        dict.remove(b"Next");
+    }
+    if matches!(dict.get(b"AA"), Ok(Object::Dictionary(triggers)) if triggers.is_empty()) {
+        dict.remove(b"AA");
+    }
+}
+
+fn sanitize_pdf(input: &Path, output: &Path, password: Option<&str>) -> Result<()> {
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let mut removed: BTreeMap<&'static str, usize> = BTreeMap::new();
+
+    let info_count = info_dict(&doc).map(|info| info.len()).unwrap_or(0);
+    if doc.trailer.remove(b"Info").is_some() {
+        *removed.entry("document info entries").or_default() += info_count;
+    }
+
+    let names_id = match doc.catalog()?.get(b"Names") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+    let names = match names_id {
+        Some(id) => doc.get_dictionary_mut(id).ok(),
+        None => doc
+            .catalog_mut()?
+            .get_mut(b"Names")
+            .and_then(Object::as_dict_mut)
+            .ok(),
+    };
+    if let Some(names) = names {
+        if names.remove(b"JavaScript").is_some() {
+            *removed.entry("document-level JavaScript").or_default() += 1;
+        }
+        if names.remove(b"EmbeddedFiles").is_some() {
+            *removed.entry("embedded file trees").or_default() += 1;
+        }
+    }
+
+    let catalog = doc.catalog_mut()?;
+    if catalog.remove(b"Collection").is_some() {
+        *removed.entry("embedded file trees").or_default() += 1;
+    }
+
+    // Indirect actions and annotations are flagged up front so references
+    // to them can be dropped wherever they appear, however deeply nested.
+    let flagged: HashMap<ObjectId, &'static str> = doc
+        .objects
+        .iter()
+        .filter_map(|(&id, object)| {
+            let dict = object.as_dict().ok()?;
+            Some((id, active_content_category(dict)?))
+        })
+        .collect();
+    for object in doc.objects.values_mut() {
+        strip_active_content(object, &flagged, &mut removed);
+    }
+
+    let pruned = prune_unreachable_objects(&mut doc);
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+
+    if removed.is_empty() {
+        println!("Nothing to remove: no metadata, scripts or attachments found.");
+    } else {
+        println!("Removed:");
+        for (category, count) in &removed {
+            println!("  • {}: {}", category, count);
+        }
+    }
+    if pruned > 0 {
+        println!("  ({} unreferenced object(s) dropped)", pruned);
+    }
+    println!("✓ Sanitized PDF saved: {}", current_output.display());
+    Ok(())
+}
+
+/// Characters of context shown on each side of a grep match.
+const GREP_CONTEXT: usize = 40;
+
+/// Byte range of `text` to print for the match at `start..end`: up to
+/// GREP_CONTEXT characters either side, stopping at line breaks and cut
+/// back to whole words.
+fn context_window(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[end..].find('\n').map_or(text.len(), |i| end + i);
+
+    let mut from = text[line_start..start]
+        .char_indices()
+        .rev()
+        .nth(GREP_CONTEXT - 1)
+        .map_or(line_start, |(i, _)| line_start + i);
+    if from > line_start && !text[..from].ends_with(char::is_whitespace) {
+        from = text[from..start]
+            .find(char::is_whitespace)
+            .map_or(start, |i| from + i);
+    }
+
+    let mut to = text[end..line_end]
+        .char_indices()
+        .nth(GREP_CONTEXT)
+        .map_or(line_end, |(i, _)| end + i);
+    if to < line_end && !text[to..].starts_with(char::is_whitespace) {
+        to = text[end..to]
+            .rfind(char::is_whitespace)
+            .map_or(end, |i| end + i);
+    }
+    (from, to)
+}
+
+fn grep_pdfs(
+    files: &[PathBuf],
+    pattern: &str,
+    ignore_case: bool,
+    files_with_matches: bool,
+    password: Option<&str>,
+) -> Result<usize> {
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .with_context(|| format!("Invalid pattern: '{}'", pattern))?;
+
+    let mut matches = 0;
+    for file in files {
+        let doc = match load_pdf(file, password) {
+            Ok(doc) => doc,
+            Err(e) => {
+                eprintln!("Error reading {}: {:#}", file.display(), e);
+                continue;
+            }
+        };
+
+        'pages: for &page_num in doc.get_pages().keys() {
+            let text = doc
+                .extract_text_chunks(&[page_num])
+                .into_iter()
+                .filter_map(|chunk| chunk.ok())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            // Overlapping windows are merged so each hit prints once.
+            let mut windows: Vec<(usize, usize, Vec<regex::Match>)> = Vec::new();
+            for m in regex.find_iter(&text).filter(|m| !m.is_empty()) {
+                let (from, to) = context_window(&text, m.start(), m.end());
+                match windows.last_mut() {
+                    Some((_, last_to, hits)) if from < *last_to => {
+                        *last_to = (*last_to).max(to);
+                        hits.push(m);
+                    }
+                    _ => windows.push((from, to, vec![m])),
+                }
+            }
+
+            for (from, to, hits) in windows {
+                matches += 1;
+
+                if files_with_matches {
+                    println!("{}", file.display());
+                    break 'pages;
+                }
+
+                let mut highlighted = String::new();
+                if from > 0 && !text[..from].ends_with('\n') {
+                    highlighted.push('…');
+                }
+                let mut last = from;
+                for hit in hits {
+                    highlighted.push_str(&text[last..hit.start()]);
+                    highlighted.push_str(&style(hit.as_str()).red().bold().to_string());
+                    last = hit.end();
+                }
+                highlighted.push_str(&text[last..to]);
+                if to < text.len() && !text[to..].starts_with('\n') {
+                    highlighted.push('…');
+                }
+
+                println!(
+                    "{}:{}: {}",
+                    style(file.display()).magenta(),
+                    style(page_num).green(),
+                    highlighted.trim()
+                );
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+fn parse_page_ranges(spec: &str, total_pages: usize) -> Result<Vec<usize>> {
+    let mut pages = parse_page_sequence(spec, total_pages)?;
+    pages.sort_unstable();
+    pages.dedup();
+    Ok(pages)
+}
+
+/// Parses a page spec keeping the order and repetitions as written.
+fn parse_page_sequence(spec: &str, total_pages: usize) -> Result<Vec<usize>> {
+    if total_pages == 0 {
+        bail!("PDF has no pages");
+    }
+
+    let mut pages = Vec::new();
+    for part in spec.split(',') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+
+        if part.contains('-') {
+            let bounds: Vec<&str> = part.split('-').collect();
+            if bounds.len() != 2 {
+                bail!("Invalid range syntax: '{}'", part);
+            }
+
+            let start_str = bounds[0].trim();
+            let end_str = bounds[1].trim();
+
+            if start_str.is_empty() {
+                bail!("Invalid range: '{}' (page numbers must be >= 1)", part);
+            }
+
+            let start = start_str
+                .parse::<usize>()
+                .map_err(|_| anyhow::anyhow!("Invalid page number: '{}'", start_str))?;
+
+            let end = if end_str.is_empty() {
+                total_pages
+            } else {
+                end_str
+                    .parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Invalid page number: '{}'", end_str))?
+            };
+
+            if start < 1 {
+                bail!("Page numbers must be >= 1");
+            }
+            if start > total_pages {
+                bail!(
+                    "Start page {} is beyond document end ({})",
+                    start,
+                    total_pages
+                );
+            }
+            if !end_str.is_empty() && end > total_pages {
+                bail!("End page {} is beyond document end ({})", end, total_pages);
+            }
+
+            let actual_end = end.min(total_pages);
+            if start <= actual_end {
+                pages.extend(start..=actual_end);
+            } else {
                 bail!("Invalid range: '{}' (start > end)", part);
             }
-        } else {
-            let page = part
-                .parse::<usize>()
-                .map_err(|_| anyhow::anyhow!("Invalid page number: '{}'", part))?;
-            if page < 1 || page > total_pages {
+        } else {
+            let page = part
+                .parse::<usize>()
+                .map_err(|_| anyhow::anyhow!("Invalid page number: '{}'", part))?;
+            if page < 1 || page > total_pages {
+                bail!(
+                    "Page {} is out of range (PDF has {} pages)",
+                    page,
+                    total_pages
+                );
+            }
+            pages.push(page);
+        }
+    }
+
+    Ok(pages)
+}
+
+fn resolve_output_conflict(output: &Path, is_directory: bool) -> Result<Option<PathBuf>> {
+    let current_output = output.to_path_buf();
+
+    if !current_output.exists() {
+        return Ok(Some(current_output));
+    }
+
+    let output_type = if is_directory { "directory" } else { "file" };
+    print!(
+        "⚠️ Output {} '{}' already exists. Action? (Y=overwrite, R=rename, N=abort): ",
+        output_type,
+        current_output.display()
+    );
+    io::stdout().flush()?;
+    let mut choice = String::new();
+    io::stdin().read_line(&mut choice)?;
+    match choice.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(Some(current_output)),
+        "n" | "no" => {
+            println!("Aborted.");
+            Ok(None)
+        }
+        "r" | "rename" => {
+            let prompt = if is_directory {
+                "Enter a new output folder: "
+            } else {
+                "Enter a new filename or folder (e.g., report.pdf or C:\\output): "
+            };
+            print!("{}", prompt);
+            io::stdout().flush()?;
+            let mut new_path = String::new();
+            io::stdin().read_line(&mut new_path)?;
+            let trimmed = new_path.trim();
+            if trimmed.is_empty() {
+                println!("Empty path. Aborted.");
+                return Ok(None);
+            }
+            let mut new_output = PathBuf::from(trimmed);
+
+            if !is_directory
+                && new_output.is_dir()
+                && let Some(filename) = output.file_name()
+            {
+                new_output.push(filename);
+            }
+
+            if new_output.exists() {
+                let msg = if is_directory {
+                    format!(
+                        "❌ Output folder '{}' already exists. Aborted to prevent overwrite.",
+                        new_output.display()
+                    )
+                } else {
+                    format!(
+                        "❌ Output '{}' already exists. Aborted to prevent overwrite.",
+                        new_output.display()
+                    )
+                };
+                println!("{}", msg);
+                return Ok(None);
+            }
+
+            Ok(Some(new_output))
+        }
+        _ => {
+            println!("Invalid choice. Aborted.");
+            Ok(None)
+        }
+    }
+}
+
+fn prepare_output_file(output: &Path) -> Result<Option<PathBuf>> {
+    let Some(current_output) = resolve_output_conflict(output, false)? else {
+        return Ok(None);
+    };
+
+    let current_output = match current_output.extension().and_then(|e| e.to_str()) {
+        Some("pdf") => current_output,
+        _ => current_output.with_extension("pdf"),
+    };
+    Ok(Some(current_output))
+}
+
+fn default_output(input: &Path, suffix: &str) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    PathBuf::from(format!("{}_{}.pdf", stem, suffix))
+}
+
+fn parse_pdf_version(version: &str) -> Result<(u8, u8)> {
+    let parsed = version
+        .trim()
+        .split_once('.')
+        .and_then(|(major, minor)| Some((major.parse::<u8>().ok()?, minor.parse::<u8>().ok()?)));
+    match parsed {
+        Some(v @ (1, 0..=7)) | Some(v @ (2, 0)) => Ok(v),
+        _ => bail!(
+            "Invalid PDF version: '{}' (expected 1.0 to 1.7 or 2.0)",
+            version
+        ),
+    }
+}
+
+/// Effective version of a document: the later of the header version and
+/// the catalog's /Version entry.
+fn document_version(doc: &Document) -> (u8, u8) {
+    let header = parse_pdf_version(&doc.version).unwrap_or((1, 0));
+    let catalog = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Version").ok())
+        .and_then(|version| version.as_name().ok())
+        .and_then(|name| parse_pdf_version(&String::from_utf8_lossy(name)).ok())
+        .unwrap_or((1, 0));
+    header.max(catalog)
+}
+
+/// Lists features present in `doc` together with the PDF version that
+/// introduced them.
+fn versioned_features(doc: &Document) -> BTreeSet<((u8, u8), &'static str)> {
+    let mut features = BTreeSet::new();
+
+    if let Ok(catalog) = doc.catalog() {
+        if catalog.has(b"OCProperties") {
+            features.insert(((1, 5), "optional content (layers)"));
+        }
+        if catalog.has(b"Collection") {
+            features.insert(((1, 7), "portable collections"));
+        }
+        if catalog.has(b"DPartRoot") {
+            features.insert(((2, 0), "document parts"));
+        }
+    }
+
+    for obj in doc.objects.values() {
+        let dict = match obj {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &stream.dict,
+            _ => continue,
+        };
+
+        if dict.has_type(b"Metadata") {
+            features.insert(((1, 4), "XMP metadata"));
+        }
+        if dict.has(b"SMask") {
+            features.insert(((1, 4), "soft masks"));
+        }
+        if let Ok(group) = dict.get(b"Group")
+            && let Ok((_, Object::Dictionary(group))) = doc.dereference(group)
+            && matches!(group.get(b"S"), Ok(Object::Name(s)) if s == b"Transparency")
+        {
+            features.insert(((1, 4), "transparency groups"));
+        }
+        if matches!(dict.get(b"Subtype"), Ok(Object::Name(s)) if s == b"3D") {
+            features.insert(((1, 6), "3D annotations"));
+        }
+        if dict.has(b"AF") {
+            features.insert(((2, 0), "associated files"));
+        }
+        match dict.get(b"CFM") {
+            Ok(Object::Name(cfm)) if cfm == b"AESV2" => {
+                features.insert(((1, 6), "AES-128 encryption"));
+            }
+            Ok(Object::Name(cfm)) if cfm == b"AESV3" => {
+                features.insert(((2, 0), "AES-256 encryption"));
+            }
+            _ => {}
+        }
+
+        if let Object::Stream(stream) = obj
+            && let Ok(filters) = stream.filters()
+        {
+            for filter in filters {
+                match filter {
+                    b"JBIG2Decode" => {
+                        features.insert(((1, 4), "JBIG2 images"));
+                    }
+                    b"JPXDecode" => {
+                        features.insert(((1, 5), "JPEG 2000 images"));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    features
+}
+
+/// Sets the header version of a newly built document, defaulting to the
+/// highest source version and warning when an explicit `requested`
+/// version is too old for features the document uses.
+fn set_output_version(
+    doc: &mut Document,
+    source_version: (u8, u8),
+    requested: Option<&str>,
+) -> Result<()> {
+    let version = match requested {
+        Some(requested) => {
+            let requested = parse_pdf_version(requested)?;
+            if requested < source_version {
+                for (needed, feature) in versioned_features(doc) {
+                    if needed > requested {
+                        println!(
+                            "⚠️ Warning: PDF {}.{} does not support {} (requires {}.{})",
+                            requested.0, requested.1, feature, needed.0, needed.1
+                        );
+                    }
+                }
+            }
+            requested
+        }
+        None => source_version,
+    };
+
+    doc.version = format!("{}.{}", version.0, version.1);
+    if version < (1, 5) {
+        doc.reference_table.cross_reference_type = XrefType::CrossReferenceTable;
+    }
+    Ok(())
+}
+
+/// Looks up an inheritable page attribute (/Rotate, /MediaBox, /CropBox,
+/// /Resources), walking up the page tree when the page itself lacks it.
+fn inherited_page_attribute<'a>(
+    doc: &'a Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let value = inherited_page_entry(doc, page_id, key)?;
+    doc.dereference(value).ok().map(|(_, value)| value)
+}
+
+/// Like `inherited_page_attribute`, but returns the entry as written,
+/// which may be a reference.
+fn inherited_page_entry<'a>(
+    doc: &'a Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut node_id = page_id;
+    let mut visited = HashSet::new();
+    while visited.insert(node_id) {
+        let node = doc.get_dictionary(node_id).ok()?;
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        node_id = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+    }
+    None
+}
+
+fn page_rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    inherited_page_attribute(doc, page_id, b"Rotate")
+        .and_then(|rotate| rotate.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360)
+}
+
+fn parse_rotation_angle(angle: &str) -> Result<i64> {
+    let value = angle
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| anyhow::anyhow!("Invalid angle: '{}'", angle))?;
+    if value % 90 != 0 {
+        bail!("Invalid angle: {} (must be a multiple of 90)", value);
+    }
+    Ok(value)
+}
+
+fn rotate_pdf(
+    input: &Path,
+    output: &Path,
+    angle: i64,
+    absolute: bool,
+    pages_spec: Option<&str>,
+    password: Option<&str>,
+) -> Result<()> {
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let page_numbers = match pages_spec {
+        Some(spec) => parse_page_ranges(spec, total_pages)?,
+        None => (1..=total_pages).collect(),
+    };
+    if page_numbers.is_empty() {
+        bail!("No pages to rotate (check your page range)");
+    }
+
+    for &page_num in &page_numbers {
+        let page_id = pages[&(page_num as u32)];
+        let rotation = if absolute {
+            angle.rem_euclid(360)
+        } else {
+            (page_rotation(&doc, page_id) + angle).rem_euclid(360)
+        };
+        doc.get_dictionary_mut(page_id)?
+            .set("Rotate", Object::Integer(rotation));
+    }
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Rotated {} page(s) {} {}°: {}",
+        page_numbers.len(),
+        if absolute { "to" } else { "by" },
+        angle,
+        current_output.display()
+    );
+    Ok(())
+}
+
+/// Replaces the document's page tree with a single flat /Pages node holding
+/// `page_ids` in order. Inheritable attributes are copied onto each page
+/// first, as references where the tree holds them indirectly, so that
+/// dropping intermediate nodes does not change any page.
+fn set_page_order(doc: &mut Document, page_ids: &[ObjectId]) -> Result<()> {
+    for &page_id in page_ids {
+        let mut inherited = Vec::new();
+        for key in [&b"Resources"[..], b"MediaBox", b"CropBox", b"Rotate"] {
+            if !doc.get_dictionary(page_id)?.has(key)
+                && let Some(value) = inherited_page_entry(doc, page_id, key)
+            {
+                inherited.push((key.to_vec(), value.clone()));
+            }
+        }
+        let page = doc.get_dictionary_mut(page_id)?;
+        for (key, value) in inherited {
+            page.set(key, value);
+        }
+    }
+
+    let pages_id = doc.catalog()?.get(b"Pages")?.as_reference()?;
+    let kids: Vec<Object> = page_ids.iter().map(|&id| Object::Reference(id)).collect();
+    let pages = doc.get_dictionary_mut(pages_id)?;
+    pages.set("Kids", Object::Array(kids));
+    pages.set("Count", Object::Integer(page_ids.len() as i64));
+    pages.remove(b"Parent");
+
+    for &page_id in page_ids {
+        doc.get_dictionary_mut(page_id)?
+            .set("Parent", Object::Reference(pages_id));
+    }
+    Ok(())
+}
+
+/// Whether the explicit destination `dest` is on one of the pages `ids`.
+fn destination_removed(dest: &Object, ids: &HashSet<ObjectId>) -> bool {
+    match dest {
+        Object::Array(items) => {
+            matches!(items.first(), Some(Object::Reference(id)) if ids.contains(id))
+        }
+        _ => false,
+    }
+}
+
+/// Whether `action` is a GoTo to one of the pages `ids`.
+fn goto_removed(action: &Dictionary, ids: &HashSet<ObjectId>) -> bool {
+    action.get(b"S").and_then(Object::as_name).ok() == Some(b"GoTo")
+        && action
+            .get(b"D")
+            .is_ok_and(|dest| destination_removed(dest, ids))
+}
+
+/// Whether `dict` (a link annotation or outline item) jumps to one of the
+/// pages `ids`, through /Dest or a GoTo action, inline or in `dead_actions`.
+fn targets_removed(
+    dict: &Dictionary,
+    ids: &HashSet<ObjectId>,
+    dead_actions: &HashSet<ObjectId>,
+) -> bool {
+    if dict
+        .get(b"Dest")
+        .is_ok_and(|dest| destination_removed(dest, ids))
+    {
+        return true;
+    }
+    match dict.get(b"A") {
+        Ok(Object::Reference(id)) => dead_actions.contains(id),
+        Ok(Object::Dictionary(action)) => goto_removed(action, ids),
+        _ => false,
+    }
+}
+
+/// Removes link annotations that jump to one of `ids` and clears the
+/// destination of outline items that do, then replaces every other
+/// reference to them with null so nothing keeps the removed objects alive.
+fn null_references_to(doc: &mut Document, ids: &HashSet<ObjectId>) {
+    let dead_actions: HashSet<ObjectId> = doc
+        .objects
+        .iter()
+        .filter(|(_, object)| {
+            object
+                .as_dict()
+                .is_ok_and(|action| goto_removed(action, ids))
+        })
+        .map(|(&id, _)| id)
+        .collect();
+    let is_dead_link = |annot: &Dictionary| {
+        annot.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Link")
+            && targets_removed(annot, ids, &dead_actions)
+    };
+    let dead_links: HashSet<ObjectId> = doc
+        .objects
+        .iter()
+        .filter(|(_, object)| object.as_dict().is_ok_and(is_dead_link))
+        .map(|(&id, _)| id)
+        .collect();
+
+    for object in doc.objects.values_mut() {
+        let Ok(dict) = object.as_dict_mut() else {
+            continue;
+        };
+        if let Ok(Object::Array(annots)) = dict.get_mut(b"Annots") {
+            annots.retain(|annot| match annot {
+                Object::Reference(id) => !dead_links.contains(id),
+                Object::Dictionary(annot) => !is_dead_link(annot),
+                _ => true,
+            });
+        }
+        if dict.has(b"Title") && dict.has(b"Parent") && targets_removed(dict, ids, &dead_actions) {
+            dict.remove(b"Dest");
+            dict.remove(b"A");
+        }
+    }
+
+    fn visit(obj: &mut Object, ids: &HashSet<ObjectId>) {
+        match obj {
+            Object::Reference(id) if ids.contains(id) => *obj = Object::Null,
+            Object::Array(items) => items.iter_mut().for_each(|item| visit(item, ids)),
+            Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, val)| visit(val, ids)),
+            Object::Stream(stream) => stream.dict.iter_mut().for_each(|(_, val)| visit(val, ids)),
+            _ => {}
+        }
+    }
+
+    for obj in doc.objects.values_mut() {
+        visit(obj, ids);
+    }
+}
+
+fn delete_pages(
+    input: &Path,
+    output: &Path,
+    pages_spec: &str,
+    password: Option<&str>,
+) -> Result<()> {
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let page_numbers = parse_page_ranges(pages_spec, total_pages)?;
+    if page_numbers.is_empty() {
+        bail!("No pages to delete (check your page range)");
+    }
+    if page_numbers.len() == total_pages {
+        bail!("Refusing to delete every page of the document");
+    }
+
+    let deleted: HashSet<ObjectId> = page_numbers
+        .iter()
+        .map(|&page_num| pages[&(page_num as u32)])
+        .collect();
+    let remaining: Vec<ObjectId> = pages
+        .values()
+        .copied()
+        .filter(|id| !deleted.contains(id))
+        .collect();
+
+    set_page_order(&mut doc, &remaining)?;
+    null_references_to(&mut doc, &deleted);
+    let pruned = prune_unreachable_objects(&mut doc);
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+
+    let size_before = std::fs::metadata(input).map(|m| m.len()).unwrap_or(0);
+    let size_after = std::fs::metadata(&current_output)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    println!(
+        "Deleted {} page(s), {} remaining; dropped {} unused object(s) ({} → {} bytes)",
+        page_numbers.len(),
+        remaining.len(),
+        pruned,
+        size_before,
+        size_after
+    );
+    println!("✓ PDF saved: {}", current_output.display());
+    Ok(())
+}
+
+fn reorder_pages(
+    input: &Path,
+    output: &Path,
+    order_spec: Option<&str>,
+    reverse: bool,
+    allow_duplicates: bool,
+    password: Option<&str>,
+) -> Result<()> {
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let mut order = match order_spec {
+        Some(spec) => parse_page_sequence(spec, total_pages)?,
+        None => (1..=total_pages).collect(),
+    };
+    if reverse {
+        order.reverse();
+    }
+
+    let mut seen = vec![0usize; total_pages + 1];
+    for &page_num in &order {
+        seen[page_num] += 1;
+    }
+    let duplicated: Vec<usize> = (1..=total_pages).filter(|&p| seen[p] > 1).collect();
+    let missing: Vec<usize> = (1..=total_pages).filter(|&p| seen[p] == 0).collect();
+    if !duplicated.is_empty() && !allow_duplicates {
+        bail!(
+            "Pages listed more than once: {:?} (use --allow-duplicates to repeat pages)",
+            duplicated
+        );
+    }
+    if !missing.is_empty() {
+        bail!(
+            "Order must include every page; missing: {:?} (use 'delete' to drop pages)",
+            missing
+        );
+    }
+
+    let mut used = HashSet::new();
+    let mut page_ids = Vec::with_capacity(order.len());
+    for &page_num in &order {
+        let page_id = pages[&(page_num as u32)];
+        page_ids.push(unique_page_ref(&mut doc, &mut used, page_id)?);
+    }
+
+    set_page_order(&mut doc, &page_ids)?;
+    prune_unreachable_objects(&mut doc);
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Reordered {} page(s): {}",
+        page_ids.len(),
+        current_output.display()
+    );
+    Ok(())
+}
+
+/// Copies every object of `source` into `target` under fresh ids and
+/// returns the old-to-new id mapping.
+fn import_objects(target: &mut Document, source: &Document) -> Result<HashMap<ObjectId, ObjectId>> {
+    let mut id_map: HashMap<ObjectId, ObjectId> = HashMap::new();
+    let mut new_id = target.max_id + 1;
+
+    for &old_id in source.objects.keys() {
+        id_map.insert(old_id, (new_id, 0));
+        new_id += 1;
+    }
+    target.max_id = new_id - 1;
+
+    for (&old_id, obj) in source.objects.iter() {
+        let new_id = id_map[&old_id];
+        let mut cloned = obj.clone();
+        update_references_in_object(&mut cloned, &id_map)?;
+        target.objects.insert(new_id, cloned);
+    }
+
+    Ok(id_map)
+}
+
+/// Returns `page_id` the first time it is used and a shallow copy of the
+/// page afterwards, since a page object may only appear once in the tree.
+fn unique_page_ref(
+    doc: &mut Document,
+    used: &mut HashSet<ObjectId>,
+    page_id: ObjectId,
+) -> Result<ObjectId> {
+    if used.insert(page_id) {
+        return Ok(page_id);
+    }
+    let copy = doc.get_dictionary(page_id)?.clone();
+    Ok(doc.add_object(copy))
+}
+
+/// Splits `other.pdf:2-3` into the file and its page spec. Only a suffix
+/// following a `.pdf` name is treated as a spec, so Windows drive letters
+/// are left intact.
+fn split_source_spec(source: &str) -> (PathBuf, Option<String>) {
+    if let Some((path, spec)) = source.rsplit_once(':')
+        && path.to_ascii_lowercase().ends_with(".pdf")
+        && !spec.is_empty()
+    {
+        return (PathBuf::from(path), Some(spec.to_string()));
+    }
+    (PathBuf::from(source), None)
+}
+
+fn insert_pages(
+    input: &Path,
+    output: &Path,
+    position: usize,
+    source: &str,
+    password: Option<&str>,
+) -> Result<()> {
+    let (source_path, source_spec) = split_source_spec(source);
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+    if position == 0 {
+        bail!(
+            "Page positions are 1-based: use --at 1 or --after 0 to insert before the first page"
+        );
+    }
+    if position > total_pages + 1 {
+        bail!(
+            "Insert position {} is beyond document end ({})",
+            position,
+            total_pages
+        );
+    }
+
+    let other = load_pdf(&source_path, password)?;
+    let other_pages = other.get_pages();
+    let other_numbers = match source_spec.as_deref() {
+        Some(spec) => parse_page_sequence(spec, other_pages.len())?,
+        None => (1..=other_pages.len()).collect(),
+    };
+    if other_numbers.is_empty() {
+        bail!("No pages to insert from {}", source_path.display());
+    }
+
+    let id_map = import_objects(&mut doc, &other)?;
+    let version = document_version(&doc).max(document_version(&other));
+    doc.version = format!("{}.{}", version.0, version.1);
+
+    let mut used: HashSet<ObjectId> = pages.values().copied().collect();
+    let mut inserted = Vec::with_capacity(other_numbers.len());
+    for &page_num in &other_numbers {
+        let page_id = id_map[&other_pages[&(page_num as u32)]];
+        inserted.push(unique_page_ref(&mut doc, &mut used, page_id)?);
+    }
+
+    let mut page_ids: Vec<ObjectId> = pages.values().copied().collect();
+    page_ids.splice(position - 1..position - 1, inserted);
+    set_page_order(&mut doc, &page_ids)?;
+    prune_unreachable_objects(&mut doc);
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Inserted {} page(s) from {} at position {}: {}",
+        other_numbers.len(),
+        source_path.display(),
+        position,
+        current_output.display()
+    );
+    Ok(())
+}
+
+const PAPER_SIZES: [(&str, f32, f32); 12] = [
+    ("A0", 2383.94, 3370.39),
+    ("A1", 1683.78, 2383.94),
+    ("A2", 1190.55, 1683.78),
+    ("A3", 841.89, 1190.55),
+    ("A4", 595.28, 841.89),
+    ("A5", 419.53, 595.28),
+    ("A6", 297.64, 419.53),
+    ("B5", 498.9, 708.66),
+    ("Letter", 612.0, 792.0),
+    ("Legal", 612.0, 1008.0),
+    ("Tabloid", 792.0, 1224.0),
+    ("Ledger", 1224.0, 792.0),
+];
+
+/// Parses a length such as `36`, `36pt`, `12.7mm`, `1cm` or `0.5in` into points.
+fn parse_length(value: &str) -> Result<f32> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| anyhow::anyhow!("Invalid length: '{}'", value))?;
+    let scale = match unit.to_ascii_lowercase().as_str() {
+        "" | "pt" => 1.0,
+        "mm" => 72.0 / 25.4,
+        "cm" => 72.0 / 2.54,
+        "in" => 72.0,
+        _ => bail!("Unknown unit in '{}' (use pt, mm, cm or in)", value),
+    };
+    Ok(number * scale)
+}
+
+/// Parses a paper size: a name like `A4` or `Letter` (append `-landscape`
+/// to swap sides) or explicit dimensions like `210x297mm` or `612x792`.
+fn parse_page_size(value: &str) -> Result<(f32, f32)> {
+    let value = value.trim();
+    let (name, landscape) = match value.to_ascii_lowercase().strip_suffix("-landscape") {
+        Some(name) => (value[..name.len()].to_string(), true),
+        None => (value.to_string(), false),
+    };
+
+    let size = if let Some(&(_, width, height)) = PAPER_SIZES
+        .iter()
+        .find(|(paper, _, _)| paper.eq_ignore_ascii_case(&name))
+    {
+        (width, height)
+    } else if let Some((width, height)) = name.to_ascii_lowercase().split_once('x') {
+        let unit: String = height
+            .chars()
+            .skip_while(|c| c.is_ascii_digit() || *c == '.')
+            .collect();
+        let width = parse_length(&format!("{}{}", width, unit))?;
+        let height = parse_length(height)?;
+        if width <= 0.0 || height <= 0.0 {
+            bail!("Invalid page size: '{}'", value);
+        }
+        (width, height)
+    } else {
+        let names: Vec<&str> = PAPER_SIZES.iter().map(|(name, _, _)| *name).collect();
+        bail!(
+            "Unknown page size: '{}' (use {} or WIDTHxHEIGHT[pt|mm|cm|in])",
+            value,
+            names.join(", ")
+        );
+    };
+
+    Ok(if landscape { (size.1, size.0) } else { size })
+}
+
+fn object_as_f32(obj: &Object) -> Option<f32> {
+    match obj {
+        Object::Integer(i) => Some(*i as f32),
+        Object::Real(r) => Some(*r),
+        _ => None,
+    }
+}
+
+/// Returns a page box as `[llx, lly, urx, ury]`, falling back to the
+/// MediaBox (and to US Letter when even that is missing).
+fn page_box(doc: &Document, page_id: ObjectId, key: &[u8]) -> [f32; 4] {
+    let rect = inherited_page_attribute(doc, page_id, key)
+        .or_else(|| inherited_page_attribute(doc, page_id, b"MediaBox"))
+        .and_then(|rect| rect.as_array().ok())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    doc.dereference(item)
+                        .ok()
+                        .and_then(|(_, v)| object_as_f32(v))
+                })
+                .collect::<Vec<f32>>()
+        });
+    match rect.as_deref() {
+        Some(&[x0, y0, x1, y1]) => [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)],
+        _ => [0.0, 0.0, 612.0, 792.0],
+    }
+}
+
+fn rect_object(rect: [f32; 4]) -> Object {
+    Object::Array(rect.iter().map(|&v| Object::Real(v)).collect())
+}
+
+/// Adds an empty page object. Its size comes from `size` when given,
+/// otherwise from `neighbour` (including its rotation).
+fn add_blank_page(
+    doc: &mut Document,
+    size: Option<(f32, f32)>,
+    neighbour: Option<ObjectId>,
+) -> ObjectId {
+    let mut page = Dictionary::new();
+    page.set("Type", Object::Name(b"Page".to_vec()));
+    page.set("Resources", Object::Dictionary(Dictionary::new()));
+    match (size, neighbour) {
+        (Some((width, height)), _) => {
+            page.set("MediaBox", rect_object([0.0, 0.0, width, height]));
+        }
+        (None, Some(neighbour)) => {
+            page.set(
+                "MediaBox",
+                rect_object(page_box(doc, neighbour, b"MediaBox")),
+            );
+            let rotation = page_rotation(doc, neighbour);
+            if rotation != 0 {
+                page.set("Rotate", Object::Integer(rotation));
+            }
+        }
+        (None, None) => {
+            page.set("MediaBox", rect_object([0.0, 0.0, 612.0, 792.0]));
+        }
+    }
+    doc.add_object(page)
+}
+
+/// Looks up `name` in a name tree (e.g. /Dests or /EmbeddedFiles).
+fn lookup_name_tree<'a>(doc: &'a Document, node: &'a Object, name: &[u8]) -> Option<&'a Object> {
+    let (_, node) = doc.dereference(node).ok()?;
+    let node = node.as_dict().ok()?;
+
+    if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+        for pair in names.chunks(2) {
+            if let [Object::String(key, _), value] = pair
+                && key == name
+            {
+                return Some(value);
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+        return kids.iter().find_map(|kid| lookup_name_tree(doc, kid, name));
+    }
+    None
+}
+
+/// Resolves an outline or link destination (explicit array, named
+/// destination or GoTo action) to its explicit destination array.
+fn resolve_destination<'a>(doc: &'a Document, dest: &'a Object) -> Option<&'a [Object]> {
+    let (_, dest) = doc.dereference(dest).ok()?;
+    match dest {
+        Object::Array(items) => Some(items),
+        Object::Dictionary(dict) => resolve_destination(doc, dict.get(b"D").ok()?),
+        Object::Name(name) | Object::String(name, _) => {
+            let catalog = doc.catalog().ok()?;
+            let target = catalog
+                .get(b"Dests")
+                .ok()
+                .and_then(|dests| doc.dereference(dests).ok())
+                .and_then(|(_, dests)| dests.as_dict().ok())
+                .and_then(|dests| dests.get(name).ok())
+                .or_else(|| {
+                    let names = catalog.get(b"Names").ok()?;
+                    let (_, names) = doc.dereference(names).ok()?;
+                    let tree = names.as_dict().ok()?.get(b"Dests").ok()?;
+                    lookup_name_tree(doc, tree, name)
+                })?;
+            resolve_destination(doc, target)
+        }
+        _ => None,
+    }
+}
+
+/// Resolves an outline or link destination to a 1-based page number.
+fn resolve_destination_page(
+    doc: &Document,
+    dest: &Object,
+    page_numbers: &HashMap<ObjectId, u32>,
+) -> Option<u32> {
+    match resolve_destination(doc, dest)?.first()? {
+        Object::Reference(page_id) => page_numbers.get(page_id).copied(),
+        Object::Integer(index) => Some(*index as u32 + 1),
+        _ => None,
+    }
+}
+
+/// First page of each top-level outline entry, sorted.
+fn outline_chapter_starts(doc: &Document) -> Vec<usize> {
+    let page_numbers: HashMap<ObjectId, u32> = doc
+        .get_pages()
+        .into_iter()
+        .map(|(num, id)| (id, num))
+        .collect();
+
+    let mut starts = Vec::new();
+    let mut item = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|outlines| doc.dereference(outlines).ok())
+        .and_then(|(_, outlines)| outlines.as_dict().ok())
+        .and_then(|outlines| outlines.get(b"First").ok())
+        .and_then(|first| first.as_reference().ok());
+
+    let mut visited = HashSet::new();
+    while let Some(item_id) = item {
+        if !visited.insert(item_id) {
+            break;
+        }
+        let Ok(entry) = doc.get_dictionary(item_id) else {
+            break;
+        };
+        let dest = entry.get(b"Dest").or_else(|_| entry.get(b"A"));
+        if let Ok(dest) = dest
+            && let Some(page) = resolve_destination_page(doc, dest, &page_numbers)
+        {
+            starts.push(page as usize);
+        }
+        item = entry.get(b"Next").and_then(Object::as_reference).ok();
+    }
+
+    starts.sort_unstable();
+    starts.dedup();
+    starts
+}
+
+fn insert_blank_pages(
+    input: &Path,
+    output: &Path,
+    before: Option<&str>,
+    after: Option<&str>,
+    odd_chapters: Option<&str>,
+    size: Option<&str>,
+    password: Option<&str>,
+) -> Result<()> {
+    let size = size.map(parse_page_size).transpose()?;
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages: Vec<ObjectId> = doc.get_pages().values().copied().collect();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let before: HashSet<usize> = match before {
+        Some(spec) => parse_page_ranges(spec, total_pages)?.into_iter().collect(),
+        None => HashSet::new(),
+    };
+    let after: HashSet<usize> = match after {
+        Some(spec) => parse_page_ranges(spec, total_pages)?.into_iter().collect(),
+        None => HashSet::new(),
+    };
+
+    let chapter_starts: HashSet<usize> = match odd_chapters {
+        Some("outline") => {
+            let starts = outline_chapter_starts(&doc);
+            if starts.is_empty() {
+                bail!("No bookmarks found to detect chapters; list chapter start pages instead");
+            }
+            starts.into_iter().collect()
+        }
+        Some(chapters) => parse_page_ranges(chapters, total_pages)?
+            .into_iter()
+            .collect(),
+        None => HashSet::new(),
+    };
+
+    let mut page_ids = Vec::with_capacity(total_pages + before.len() + after.len());
+    let mut added = 0;
+    for (index, &page_id) in pages.iter().enumerate() {
+        let page_num = index + 1;
+        // Pad the previous chapter so this one starts on an odd output
+        // page, counting the blanks --before/--after have already added.
+        if page_num > 1 && chapter_starts.contains(&page_num) {
+            let position = page_ids.len() + usize::from(before.contains(&page_num)) + 1;
+            if position % 2 == 0 {
+                page_ids.push(add_blank_page(&mut doc, size, Some(pages[index - 1])));
+                added += 1;
+            }
+        }
+        if before.contains(&page_num) {
+            page_ids.push(add_blank_page(&mut doc, size, Some(page_id)));
+            added += 1;
+        }
+        page_ids.push(page_id);
+        if after.contains(&page_num) {
+            page_ids.push(add_blank_page(&mut doc, size, Some(page_id)));
+            added += 1;
+        }
+    }
+    if odd_chapters.is_some() && page_ids.len() % 2 == 1 {
+        let last = pages[total_pages - 1];
+        page_ids.push(add_blank_page(&mut doc, size, Some(last)));
+        added += 1;
+    }
+
+    set_page_order(&mut doc, &page_ids)?;
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Added {} blank page(s), {} pages total: {}",
+        added,
+        page_ids.len(),
+        current_output.display()
+    );
+    Ok(())
+}
+
+fn page_box_key(name: &str) -> Result<&'static str> {
+    Ok(match name.to_ascii_lowercase().as_str() {
+        "media" | "mediabox" => "MediaBox",
+        "crop" | "cropbox" => "CropBox",
+        "trim" | "trimbox" => "TrimBox",
+        "bleed" | "bleedbox" => "BleedBox",
+        "art" | "artbox" => "ArtBox",
+        _ => bail!(
+            "Unknown page box: '{}' (use media, crop, trim, bleed or art)",
+            name
+        ),
+    })
+}
+
+fn parse_lengths(spec: &str) -> Result<Vec<f32>> {
+    spec.split(',').map(parse_length).collect()
+}
+
+/// Expands 1, 2 or 4 margin values (CSS order: top, right, bottom, left).
+fn parse_margins(spec: &str) -> Result<[f32; 4]> {
+    match parse_lengths(spec)?[..] {
+        [all] => Ok([all; 4]),
+        [vertical, horizontal] => Ok([vertical, horizontal, vertical, horizontal]),
+        [top, right, bottom, left] => Ok([top, right, bottom, left]),
+        _ => bail!(
+            "Invalid margins: '{}' (give 1, 2 or 4 comma-separated values)",
+            spec
+        ),
+    }
+}
+
+enum CropGeometry {
+    Rect([f32; 4]),
+    Margins([f32; 4]),
+    Size((f32, f32)),
+}
+
+/// Computes the new box for one page, clamped to its `media` box. Margins
+/// and sizes are given as the page is displayed, so they are mapped through
+/// the page's /Rotate.
+fn cropped_box(
+    base: [f32; 4],
+    media: [f32; 4],
+    rotation: i64,
+    geometry: &CropGeometry,
+) -> [f32; 4] {
+    let [x0, y0, x1, y1] = base;
+    let rect = match geometry {
+        CropGeometry::Rect(rect) => *rect,
+        CropGeometry::Margins(visual) => {
+            let turns = (rotation / 90) as usize;
+            let margin = |side: usize| visual[(side + turns) % 4];
+            [
+                x0 + margin(3),
+                y0 + margin(2),
+                x1 - margin(1),
+                y1 - margin(0),
+            ]
+        }
+        CropGeometry::Size((width, height)) => {
+            let (width, height) = if rotation % 180 == 0 {
+                (*width, *height)
+            } else {
+                (*height, *width)
+            };
+            let cx = (x0 + x1) / 2.0;
+            let cy = (y0 + y1) / 2.0;
+            [
+                cx - width / 2.0,
+                cy - height / 2.0,
+                cx + width / 2.0,
+                cy + height / 2.0,
+            ]
+        }
+    };
+    [
+        rect[0].max(media[0]),
+        rect[1].max(media[1]),
+        rect[2].min(media[2]),
+        rect[3].min(media[3]),
+    ]
+}
+
+fn crop_pdf(
+    input: &Path,
+    output: &Path,
+    pages_spec: Option<&str>,
+    boxes: &[String],
+    geometry: &CropGeometry,
+    password: Option<&str>,
+) -> Result<()> {
+    let keys = boxes
+        .iter()
+        .map(|name| page_box_key(name))
+        .collect::<Result<Vec<_>>>()?;
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let page_numbers = match pages_spec {
+        Some(spec) => parse_page_ranges(spec, total_pages)?,
+        None => (1..=total_pages).collect(),
+    };
+    if page_numbers.is_empty() {
+        bail!("No pages to crop (check your page range)");
+    }
+
+    for &page_num in &page_numbers {
+        let page_id = pages[&(page_num as u32)];
+        let base = page_box(&doc, page_id, b"CropBox");
+        let media = page_box(&doc, page_id, b"MediaBox");
+        let rect = cropped_box(base, media, page_rotation(&doc, page_id), geometry);
+        if rect[2] - rect[0] < 1.0 || rect[3] - rect[1] < 1.0 {
+            bail!(
+                "Crop leaves page {} empty ({:.1} x {:.1} pt within its MediaBox)",
+                page_num,
+                rect[2] - rect[0],
+                rect[3] - rect[1]
+            );
+        }
+
+        let page = doc.get_dictionary_mut(page_id)?;
+        for key in &keys {
+            page.set(*key, rect_object(rect));
+        }
+    }
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Set {} on {} page(s): {}",
+        keys.join("/"),
+        page_numbers.len(),
+        current_output.display()
+    );
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum ResizeMode {
+    Fit,
+    Fill,
+    Center,
+}
+
+fn parse_resize_mode(value: &str) -> Result<ResizeMode> {
+    Ok(match value.to_ascii_lowercase().as_str() {
+        "fit" => ResizeMode::Fit,
+        "fill" => ResizeMode::Fill,
+        "center" => ResizeMode::Center,
+        _ => bail!("Unknown resize mode: '{}' (use fit, fill or center)", value),
+    })
+}
+
+/// Wraps a page's existing content streams between `before` and `after`,
+/// leaving the original streams untouched.
+fn wrap_page_content(
+    doc: &mut Document,
+    page_id: ObjectId,
+    before: Vec<u8>,
+    after: Vec<u8>,
+) -> Result<()> {
+    let existing = match doc.get_dictionary(page_id)?.get(b"Contents") {
+        Ok(Object::Array(items)) => items.clone(),
+        Ok(contents) => vec![contents.clone()],
+        Err(_) => Vec::new(),
+    };
+
+    let mut contents = Vec::with_capacity(existing.len() + 2);
+    if !before.is_empty() {
+        contents.push(Object::Reference(
+            doc.add_object(Stream::new(Dictionary::new(), before)),
+        ));
+    }
+    contents.extend(existing);
+    if !after.is_empty() {
+        contents.push(Object::Reference(
+            doc.add_object(Stream::new(Dictionary::new(), after)),
+        ));
+    }
+
+    doc.get_dictionary_mut(page_id)?
+        .set("Contents", Object::Array(contents));
+    Ok(())
+}
+
+/// Moves every coordinate entry of an annotation by `x * scale + tx`,
+/// `y * scale + ty`.
+fn transform_annotation(annotation: &mut Dictionary, scale: f32, tx: f32, ty: f32) {
+    let transform_points = |points: &mut Object| {
+        if let Object::Array(items) = points {
+            for (index, item) in items.iter_mut().enumerate() {
+                if let Some(value) = object_as_f32(item) {
+                    let offset = if index % 2 == 0 { tx } else { ty };
+                    *item = Object::Real(value * scale + offset);
+                }
+            }
+        }
+    };
+    for key in [&b"Rect"[..], b"QuadPoints", b"Vertices", b"L", b"CL"] {
+        if let Ok(points) = annotation.get_mut(key) {
+            transform_points(points);
+        }
+    }
+    if let Ok(Object::Array(strokes)) = annotation.get_mut(b"InkList") {
+        for stroke in strokes {
+            transform_points(stroke);
+        }
+    }
+}
+
+/// Scales one page onto a `width` x `height` sheet (as displayed) and
+/// returns the applied scale factor. `transformed` collects the
+/// annotations and /Annots arrays already moved, so that pages sharing
+/// them do not move them twice.
+fn resize_page(
+    doc: &mut Document,
+    page_id: ObjectId,
+    (width, height): (f32, f32),
+    mode: ResizeMode,
+    transformed: &mut HashSet<ObjectId>,
+) -> Result<f32> {
+    let [x0, y0, x1, y1] = page_box(doc, page_id, b"CropBox");
+    let (source_width, source_height) = (x1 - x0, y1 - y0);
+    if source_width <= 0.0 || source_height <= 0.0 {
+        bail!("Page has an empty visible area");
+    }
+
+    // The content lives in unrotated space, so rotated pages need the
+    // target sheet turned the same way.
+    let (width, height) = if page_rotation(doc, page_id) % 180 == 0 {
+        (width, height)
+    } else {
+        (height, width)
+    };
+
+    let scale_x = width / source_width;
+    let scale_y = height / source_height;
+    let scale = match mode {
+        ResizeMode::Fit => scale_x.min(scale_y),
+        ResizeMode::Fill => scale_x.max(scale_y),
+        ResizeMode::Center => 1.0,
+    };
+    let tx = (width - source_width * scale) / 2.0 - x0 * scale;
+    let ty = (height - source_height * scale) / 2.0 - y0 * scale;
+
+    let before = format!(
+        "q {} 0 0 {} {} {} cm {} {} {} {} re W n\n",
+        scale, scale, tx, ty, x0, y0, source_width, source_height
+    );
+    wrap_page_content(doc, page_id, before.into_bytes(), b"\nQ\n".to_vec())?;
+
+    let mut annotations = Vec::new();
+    let mut array_id = None;
+    match doc.get_dictionary(page_id)?.get(b"Annots") {
+        Ok(Object::Array(items)) => annotations = items.clone(),
+        Ok(Object::Reference(id)) if transformed.insert(*id) => {
+            array_id = Some(*id);
+            annotations = doc.get_object(*id)?.as_array()?.clone();
+        }
+        _ => {}
+    }
+    for annotation in &mut annotations {
+        match annotation {
+            Object::Reference(id) => {
+                if transformed.insert(*id)
+                    && let Ok(annotation) = doc.get_dictionary_mut(*id)
+                {
+                    transform_annotation(annotation, scale, tx, ty);
+                }
+            }
+            Object::Dictionary(annotation) => transform_annotation(annotation, scale, tx, ty),
+            _ => {}
+        }
+    }
+    if !annotations.is_empty() {
+        match array_id {
+            Some(id) => {
+                doc.objects.insert(id, Object::Array(annotations));
+            }
+            None => {
+                doc.get_dictionary_mut(page_id)?
+                    .set("Annots", Object::Array(annotations));
+            }
+        }
+    }
+
+    let page = doc.get_dictionary_mut(page_id)?;
+    page.set("MediaBox", rect_object([0.0, 0.0, width, height]));
+    for key in ["CropBox", "TrimBox", "BleedBox", "ArtBox"] {
+        page.remove(key.as_bytes());
+    }
+    Ok(scale)
+}
+
+/// Resolves the target sheet for a page, turning it to landscape for
+/// landscape pages when `keep_orientation` is set.
+fn target_sheet(
+    doc: &Document,
+    page_id: ObjectId,
+    size: (f32, f32),
+    keep_orientation: bool,
+) -> (f32, f32) {
+    if !keep_orientation {
+        return size;
+    }
+    let [x0, y0, x1, y1] = page_box(doc, page_id, b"CropBox");
+    let (mut page_width, mut page_height) = (x1 - x0, y1 - y0);
+    if page_rotation(doc, page_id) % 180 != 0 {
+        std::mem::swap(&mut page_width, &mut page_height);
+    }
+    let (short, long) = (size.0.min(size.1), size.0.max(size.1));
+    if page_width > page_height {
+        (long, short)
+    } else {
+        (short, long)
+    }
+}
+
+fn resize_pdf(
+    input: &Path,
+    output: &Path,
+    pages_spec: Option<&str>,
+    size: (f32, f32),
+    mode: ResizeMode,
+    keep_orientation: bool,
+    password: Option<&str>,
+) -> Result<()> {
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let page_numbers = match pages_spec {
+        Some(spec) => parse_page_ranges(spec, total_pages)?,
+        None => (1..=total_pages).collect(),
+    };
+    if page_numbers.is_empty() {
+        bail!("No pages to resize (check your page range)");
+    }
+
+    let mut transformed = HashSet::new();
+    for &page_num in &page_numbers {
+        let page_id = pages[&(page_num as u32)];
+        let sheet = target_sheet(&doc, page_id, size, keep_orientation);
+        resize_page(&mut doc, page_id, sheet, mode, &mut transformed)
+            .with_context(|| format!("Failed to resize page {}", page_num))?;
+    }
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Resized {} page(s) to {:.0} x {:.0} pt: {}",
+        page_numbers.len(),
+        size.0,
+        size.1,
+        current_output.display()
+    );
+    Ok(())
+}
+
+/// A source page captured as a Form XObject, ready to be placed on a sheet.
+#[derive(Clone, Copy)]
+struct PageForm {
+    id: ObjectId,
+    bbox: [f32; 4],
+    rotation: i64,
+}
+
+/// Displayed size of a captured page, after its /Rotate.
+fn form_size(form: &PageForm) -> (f32, f32) {
+    let (width, height) = (form.bbox[2] - form.bbox[0], form.bbox[3] - form.bbox[1]);
+    if form.rotation % 180 == 0 {
+        (width, height)
+    } else {
+        (height, width)
+    }
+}
+
+/// Matrix that draws `form` upright at `scale`, with its displayed
+/// lower-left corner at (`x`, `y`).
+fn form_matrix(form: &PageForm, scale: f32, x: f32, y: f32) -> [f32; 6] {
+    let [x0, y0, x1, y1] = form.bbox;
+    let (width, height) = (x1 - x0, y1 - y0);
+    let [a, b, c, d, e, f] = match form.rotation {
+        90 => [0.0, -1.0, 1.0, 0.0, 0.0, width],
+        180 => [-1.0, 0.0, 0.0, -1.0, width, height],
+        270 => [0.0, 1.0, -1.0, 0.0, height, 0.0],
+        _ => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+    };
+    [
+        scale * a,
+        scale * b,
+        scale * c,
+        scale * d,
+        scale * (e - a * x0 - c * y0) + x,
+        scale * (f - b * x0 - d * y0) + y,
+    ]
+}
+
+/// Scales `form` to fit the `[x, y, width, height]` cell, centered.
+/// Returns the placement matrix and the displayed rectangle it covers.
+fn fit_form(form: &PageForm, cell: [f32; 4]) -> ([f32; 6], [f32; 4]) {
+    let [x, y, width, height] = cell;
+    let (form_width, form_height) = form_size(form);
+    let scale = (width / form_width).min(height / form_height);
+    let left = x + (width - form_width * scale) / 2.0;
+    let bottom = y + (height - form_height * scale) / 2.0;
+    (
+        form_matrix(form, scale, left, bottom),
+        [left, bottom, form_width * scale, form_height * scale],
+    )
+}
+
+fn draw_form(name: &str, matrix: [f32; 6]) -> String {
+    let [a, b, c, d, e, f] = matrix;
+    format!("q {} {} {} {} {} {} cm /{} Do Q\n", a, b, c, d, e, f, name)
+}
+
+/// Copies a page's visible area, content and resources into a Form XObject.
+fn page_to_form(doc: &mut Document, page_id: ObjectId) -> Result<PageForm> {
+    let bbox = page_box(doc, page_id, b"CropBox");
+    let rotation = page_rotation(doc, page_id);
+
+    let mut content = Vec::new();
+    for stream_id in doc.get_page_contents(page_id) {
+        if let Ok(stream) = doc.get_object(stream_id).and_then(Object::as_stream) {
+            // Unfiltered streams are used as-is; anything else must decode,
+            // since the form's content is written uncompressed.
+            if stream.dict.has(b"Filter") {
+                let data = stream.decompressed_content().with_context(|| {
+                    format!(
+                        "Failed to decode content stream {} {} R of a page",
+                        stream_id.0, stream_id.1
+                    )
+                })?;
+                content.extend(data);
+            } else {
+                content.extend(&stream.content);
+            }
+            content.push(b'\n');
+        }
+    }
+
+    let mut dict = Dictionary::new();
+    dict.set("Type", Object::Name(b"XObject".to_vec()));
+    dict.set("Subtype", Object::Name(b"Form".to_vec()));
+    dict.set("BBox", rect_object(bbox));
+    dict.set(
+        "Resources",
+        inherited_page_attribute(doc, page_id, b"Resources")
+            .cloned()
+            .unwrap_or_else(|| Object::Dictionary(Dictionary::new())),
+    );
+    if let Ok(group) = doc.get_dictionary(page_id)?.get(b"Group") {
+        dict.set("Group", group.clone());
+    }
+
+    let mut stream = Stream::new(dict, content);
+    let _ = stream.compress();
+    Ok(PageForm {
+        id: doc.add_object(stream),
+        bbox,
+        rotation,
+    })
+}
+
+/// Adds a new page of the given size that draws `content` using `xobjects`.
+fn add_sheet(
+    doc: &mut Document,
+    (width, height): (f32, f32),
+    content: String,
+    xobjects: Dictionary,
+) -> ObjectId {
+    let mut stream = Stream::new(Dictionary::new(), content.into_bytes());
+    let _ = stream.compress();
+    let content_id = doc.add_object(stream);
+
+    let mut resources = Dictionary::new();
+    resources.set("XObject", Object::Dictionary(xobjects));
+
+    let mut page = Dictionary::new();
+    page.set("Type", Object::Name(b"Page".to_vec()));
+    page.set("MediaBox", rect_object([0.0, 0.0, width, height]));
+    page.set("Resources", Object::Dictionary(resources));
+    page.set("Contents", Object::Reference(content_id));
+    doc.add_object(page)
+}
+
+/// Makes `sheets` the document's pages and drops the old pages it no longer
+/// lists, along with everything only they used.
+fn replace_pages_with_sheets(doc: &mut Document, sheets: &[ObjectId]) -> Result<usize> {
+    let mut old_pages: HashSet<ObjectId> = doc.get_pages().into_values().collect();
+    for id in sheets {
+        old_pages.remove(id);
+    }
+    set_page_order(doc, sheets)?;
+    null_references_to(doc, &old_pages);
+    Ok(prune_unreachable_objects(doc))
+}
+
+fn parse_grid(spec: &str) -> Result<(usize, usize)> {
+    let parsed = spec
+        .to_ascii_lowercase()
+        .split_once('x')
+        .and_then(|(cols, rows)| Some((cols.trim().parse().ok()?, rows.trim().parse().ok()?)));
+    match parsed {
+        Some((cols, rows)) if cols > 0 && rows > 0 => Ok((cols, rows)),
+        _ => bail!("Invalid grid: '{}' (expected COLUMNSxROWS, e.g. 2x2)", spec),
+    }
+}
+
+/// Maps the `index`-th slot on a sheet to its (column, row) for a reading order.
+fn grid_position(
+    index: usize,
+    (cols, rows): (usize, usize),
+    order: &str,
+) -> Result<(usize, usize)> {
+    Ok(match order {
+        "row" => (index % cols, index / cols),
+        "row-rtl" => (cols - 1 - index % cols, index / cols),
+        "column" => (index / rows, index % rows),
+        "column-rtl" => (cols - 1 - index / rows, index % rows),
+        _ => bail!(
+            "Unknown reading order: '{}' (use row, column, row-rtl or column-rtl)",
+            order
+        ),
+    })
+}
+
+struct NupLayout {
+    grid: (usize, usize),
+    sheet: Option<(f32, f32)>,
+    margin: f32,
+    gutter: f32,
+    order: String,
+    frame: Option<f32>,
+}
+
+fn nup_pdf(
+    input: &Path,
+    output: &Path,
+    pages_spec: Option<&str>,
+    layout: &NupLayout,
+    password: Option<&str>,
+) -> Result<()> {
+    let (cols, rows) = layout.grid;
+    grid_position(0, layout.grid, &layout.order)?;
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let page_numbers = match pages_spec {
+        Some(spec) => parse_page_ranges(spec, total_pages)?,
+        None => (1..=total_pages).collect(),
+    };
+    if page_numbers.is_empty() {
+        bail!("No pages to impose (check your page range)");
+    }
+
+    let forms = page_numbers
+        .iter()
+        .map(|&page_num| page_to_form(&mut doc, pages[&(page_num as u32)]))
+        .collect::<Result<Vec<_>>>()?;
+
+    let cell_size = |(width, height): (f32, f32)| {
+        (
+            (width - 2.0 * layout.margin - (cols - 1) as f32 * layout.gutter) / cols as f32,
+            (height - 2.0 * layout.margin - (rows - 1) as f32 * layout.gutter) / rows as f32,
+        )
+    };
+
+    // Without an explicit sheet, use the first page's size turned whichever
+    // way lets the pages be drawn largest.
+    let sheet = layout.sheet.unwrap_or_else(|| {
+        let (width, height) = form_size(&forms[0]);
+        let scale = |sheet| {
+            let (cell_width, cell_height) = cell_size(sheet);
+            (cell_width / width).min(cell_height / height)
+        };
+        if scale((height, width)) > scale((width, height)) {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    });
+    let (cell_width, cell_height) = cell_size(sheet);
+    if cell_width <= 0.0 || cell_height <= 0.0 {
+        bail!(
+            "Margins and gutter leave no room for a {}x{} grid",
+            cols,
+            rows
+        );
+    }
+
+    let mut sheets = Vec::new();
+    for chunk in forms.chunks(cols * rows) {
+        let mut content = String::new();
+        let mut xobjects = Dictionary::new();
+        for (index, form) in chunk.iter().enumerate() {
+            let (col, row) = grid_position(index, layout.grid, &layout.order)?;
+            let cell = [
+                layout.margin + col as f32 * (cell_width + layout.gutter),
+                sheet.1
+                    - layout.margin
+                    - (row + 1) as f32 * cell_height
+                    - row as f32 * layout.gutter,
+                cell_width,
+                cell_height,
+            ];
+            let name = format!("P{}", index);
+            let (matrix, [x, y, width, height]) = fit_form(form, cell);
+            content.push_str(&draw_form(&name, matrix));
+            if let Some(line_width) = layout.frame {
+                content.push_str(&format!(
+                    "q {} w {} {} {} {} re S Q\n",
+                    line_width, x, y, width, height
+                ));
+            }
+            xobjects.set(name, Object::Reference(form.id));
+        }
+        sheets.push(add_sheet(&mut doc, sheet, content, xobjects));
+    }
+
+    replace_pages_with_sheets(&mut doc, &sheets)?;
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Placed {} page(s) {}x{} on {} sheet(s): {}",
+        forms.len(),
+        cols,
+        rows,
+        sheets.len(),
+        current_output.display()
+    );
+    Ok(())
+}
+
+/// Orders padded pages into saddle-stitch sheets: each entry holds the
+/// (left, right) pages for the front and then the back of one sheet.
+fn signature_order<T: Copy>(pages: &[T]) -> Vec<[(T, T); 2]> {
+    let count = pages.len();
+    (0..count / 4)
+        .map(|sheet| {
+            let outer = 2 * sheet;
+            [
+                (pages[count - 1 - outer], pages[outer]),
+                (pages[outer + 1], pages[count - 2 - outer]),
+            ]
+        })
+        .collect()
+}
+
+struct BookletLayout {
+    sheet: Option<(f32, f32)>,
+    signature: Option<usize>,
+    creep: f32,
+    right_to_left: bool,
+}
+
+fn booklet_pdf(
+    input: &Path,
+    output: &Path,
+    pages_spec: Option<&str>,
+    layout: &BookletLayout,
+    password: Option<&str>,
+) -> Result<()> {
+    let &BookletLayout {
+        sheet,
+        signature,
+        creep,
+        right_to_left,
+    } = layout;
+    if let Some(size) = signature
+        && (size == 0 || size % 4 != 0)
+    {
+        bail!(
+            "Signature size must be a positive multiple of 4 (got {})",
+            size
+        );
+    }
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let page_numbers = match pages_spec {
+        Some(spec) => parse_page_ranges(spec, total_pages)?,
+        None => (1..=total_pages).collect(),
+    };
+    if page_numbers.is_empty() {
+        bail!("No pages to impose (check your page range)");
+    }
+
+    let mut forms = page_numbers
+        .iter()
+        .map(|&page_num| page_to_form(&mut doc, pages[&(page_num as u32)]).map(Some))
+        .collect::<Result<Vec<_>>>()?;
+    let padding = (4 - forms.len() % 4) % 4;
+    forms.resize(forms.len() + padding, None);
+    if padding > 0 {
+        println!("Added {} blank page(s) to reach {}", padding, forms.len());
+    }
+
+    let sheet = sheet.unwrap_or_else(|| {
+        let (width, height) = forms[0].as_ref().map(form_size).unwrap_or((612.0, 792.0));
+        (2.0 * width, height)
+    });
+    let half = sheet.0 / 2.0;
+
+    let mut sheets = Vec::new();
+    for chunk in forms.chunks(signature.unwrap_or(forms.len())) {
+        for (index, sides) in signature_order(chunk).into_iter().enumerate() {
+            // Inner sheets stick out further once folded, so pull their
+            // pages towards the spine.
+            let shift = creep * index as f32;
+            for (left, right) in sides {
+                let (left, right) = if right_to_left {
+                    (right, left)
+                } else {
+                    (left, right)
+                };
+                let mut content = String::new();
+                let mut xobjects = Dictionary::new();
+                for (name, form, x) in [("L", left, shift), ("R", right, half - shift)] {
+                    let Some(form) = form else {
+                        continue;
+                    };
+                    let (matrix, _) = fit_form(&form, [x, 0.0, half, sheet.1]);
+                    content.push_str(&draw_form(name, matrix));
+                    xobjects.set(name, Object::Reference(form.id));
+                }
+                sheets.push(add_sheet(&mut doc, sheet, content, xobjects));
+            }
+        }
+    }
+
+    replace_pages_with_sheets(&mut doc, &sheets)?;
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Booklet of {} page(s) on {} sheet(s), printed double-sided: {}",
+        forms.len(),
+        sheets.len() / 2,
+        current_output.display()
+    );
+    Ok(())
+}
+
+/// Draws corner crop marks just outside the `[x, y, width, height]` area.
+fn crop_marks(area: [f32; 4], margin: f32) -> String {
+    const GAP: f32 = 3.0;
+    let length = (margin - 2.0 * GAP).min(18.0);
+    if length <= 0.0 {
+        return String::new();
+    }
+    let [x, y, width, height] = area;
+    let mut content = String::from("q 0.25 w 0 G\n");
+    for (cx, dx) in [(x, -1.0), (x + width, 1.0)] {
+        for (cy, dy) in [(y, -1.0), (y + height, 1.0)] {
+            content.push_str(&format!(
+                "{} {} m {} {} l S {} {} m {} {} l S\n",
+                cx + dx * GAP,
+                cy,
+                cx + dx * (GAP + length),
+                cy,
+                cx,
+                cy + dy * GAP,
+                cx,
+                cy + dy * (GAP + length)
+            ));
+        }
+    }
+    content.push_str("Q\n");
+    content
+}
+
+struct TileLayout {
+    sheet: (f32, f32),
+    margin: f32,
+    overlap: f32,
+    scale: f32,
+    marks: bool,
+}
+
+fn tile_pdf(
+    input: &Path,
+    output: &Path,
+    pages_spec: Option<&str>,
+    layout: &TileLayout,
+    password: Option<&str>,
+) -> Result<()> {
+    let (sheet_width, sheet_height) = layout.sheet;
+    let area_width = sheet_width - 2.0 * layout.margin;
+    let area_height = sheet_height - 2.0 * layout.margin;
+    if area_width <= layout.overlap || area_height <= layout.overlap {
+        bail!("Margins and overlap leave no printable area on the sheet");
+    }
+    if layout.scale <= 0.0 {
+        bail!("Scale must be greater than 0 (got {})", layout.scale);
+    }
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let page_numbers = match pages_spec {
+        Some(spec) => parse_page_ranges(spec, total_pages)?,
+        None => (1..=total_pages).collect(),
+    };
+    if page_numbers.is_empty() {
+        bail!("No pages to tile (check your page range)");
+    }
+
+    let step_x = area_width - layout.overlap;
+    let step_y = area_height - layout.overlap;
+    let tiles_along =
+        |length: f32, step: f32| (((length - layout.overlap) / step).ceil() as usize).max(1);
+    let area = [layout.margin, layout.margin, area_width, area_height];
+    let clip = format!(
+        "{} {} {} {} re W n\n",
+        layout.margin, layout.margin, area_width, area_height
+    );
+
+    let mut new_order = Vec::new();
+    let mut tile_count = 0;
+    for (&page_num, &page_id) in &pages {
+        if !page_numbers.contains(&(page_num as usize)) {
+            new_order.push(page_id);
+            continue;
+        }
+
+        let form = page_to_form(&mut doc, page_id)?;
+        let (width, height) = form_size(&form);
+        let (width, height) = (width * layout.scale, height * layout.scale);
+        let cols = tiles_along(width, step_x);
+        let rows = tiles_along(height, step_y);
+        println!("  Page {}: {}x{} tiles", page_num, cols, rows);
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let top = height - row as f32 * step_y;
+                let matrix = form_matrix(
+                    &form,
+                    layout.scale,
+                    layout.margin - col as f32 * step_x,
+                    layout.margin + area_height - top,
+                );
+                let mut content = format!("q {}{}Q\n", clip, draw_form("Page", matrix));
+                if layout.marks {
+                    content.push_str(&crop_marks(area, layout.margin));
+                }
+                let mut xobjects = Dictionary::new();
+                xobjects.set("Page", Object::Reference(form.id));
+                new_order.push(add_sheet(&mut doc, layout.sheet, content, xobjects));
+                tile_count += 1;
+            }
+        }
+    }
+
+    replace_pages_with_sheets(&mut doc, &new_order)?;
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Tiled {} page(s) onto {} sheet(s): {}",
+        page_numbers.len(),
+        tile_count,
+        current_output.display()
+    );
+    Ok(())
+}
+
+const STANDARD_FONTS: [&str; 14] = [
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Times-Roman",
+    "Times-Bold",
+    "Times-Italic",
+    "Times-BoldItalic",
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+    "Symbol",
+    "ZapfDingbats",
+];
+
+/// Advance widths (1/1000 em) of the printable ASCII range, from the
+/// standard-14 AFM files. Oblique and italic styles reuse their upright widths.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+const TIMES_WIDTHS: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444, 921, 722, 667, 667, 722, 611,
+    556, 722, 722, 333, 389, 722, 611, 889, 722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722,
+    722, 611, 333, 278, 333, 469, 500, 333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500,
+    278, 778, 500, 500, 500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+const TIMES_BOLD_WIDTHS: [u16; 95] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500, 930, 722, 667, 722, 722, 667,
+    611, 778, 778, 389, 500, 778, 667, 944, 722, 778, 611, 778, 722, 556, 667, 722, 722, 1000, 722,
+    722, 667, 333, 278, 333, 581, 500, 333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556,
+    278, 833, 556, 500, 556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520,
+];
+
+fn parse_standard_font(name: &str) -> Result<&'static str> {
+    match STANDARD_FONTS
+        .iter()
+        .find(|font| font.eq_ignore_ascii_case(name))
+    {
+        Some(font) => Ok(font),
+        None => bail!(
+            "Unknown font: '{}' (use one of the standard 14: {})",
+            name,
+            STANDARD_FONTS.join(", ")
+        ),
+    }
+}
+
+/// Encodes text as a WinAnsi hex string; unsupported characters become '?'.
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        })
+        .collect()
+}
+
+fn text_width(font: &str, text: &str, size: f32) -> f32 {
+    let widths = if font.starts_with("Courier") {
+        None
+    } else if font.starts_with("Helvetica-Bold") {
+        Some(&HELVETICA_BOLD_WIDTHS)
+    } else if font.starts_with("Helvetica") {
+        Some(&HELVETICA_WIDTHS)
+    } else if font.starts_with("Times-Bold") {
+        Some(&TIMES_BOLD_WIDTHS)
+    } else if font.starts_with("Times") {
+        Some(&TIMES_WIDTHS)
+    } else {
+        None
+    };
+    let units: u32 = encode_win_ansi(text)
+        .into_iter()
+        .map(|byte| match (widths, byte) {
+            (Some(widths), b' '..=b'~') => widths[(byte - b' ') as usize] as u32,
+            (Some(widths), _) => widths[(b'n' - b' ') as usize] as u32,
+            (None, _) => 600,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+/// `Tj` operand for `text` in a font created by `standard_font`.
+fn pdf_text(text: &str) -> String {
+    let hex: String = encode_win_ansi(text)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    format!("<{}>", hex)
+}
+
+fn standard_font(font: &str) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.set("Type", Object::Name(b"Font".to_vec()));
+    dict.set("Subtype", Object::Name(b"Type1".to_vec()));
+    dict.set("BaseFont", Object::Name(font.as_bytes().to_vec()));
+    if font != "Symbol" && font != "ZapfDingbats" {
+        dict.set("Encoding", Object::Name(b"WinAnsiEncoding".to_vec()));
+    }
+    dict
+}
+
+fn parse_color(value: &str) -> Result<[f32; 3]> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Invalid color: '{}' (expected RRGGBB, e.g. FF0000)", value);
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0) as f32 / 255.0;
+    Ok([channel(0), channel(2), channel(4)])
+}
+
+/// Adds `value` to a page's resource `category` (Font, XObject, ExtGState)
+/// under a free name starting with `prefix`, and returns that name. The
+/// resource dictionary is updated where it lives, so pages sharing or
+/// inheriting it get the entry once and reuse its name.
+fn add_page_resource(
+    doc: &mut Document,
+    page_id: ObjectId,
+    category: &str,
+    prefix: &str,
+    value: Object,
+) -> Result<String> {
+    let mut holder = page_id;
+    let mut visited = HashSet::new();
+    while visited.insert(holder) {
+        let node = doc.get_dictionary(holder)?;
+        if node.has(b"Resources") {
+            break;
+        }
+        match node.get(b"Parent").and_then(Object::as_reference) {
+            Ok(parent) => holder = parent,
+            Err(_) => {
+                holder = page_id;
+                break;
+            }
+        }
+    }
+
+    let resources_id = match doc.get_dictionary(holder)?.get(b"Resources") {
+        Ok(Object::Reference(id)) => *id,
+        existing => {
+            let resources = existing
+                .and_then(Object::as_dict)
+                .cloned()
+                .unwrap_or_default();
+            let id = doc.add_object(resources);
+            doc.get_dictionary_mut(holder)?.set("Resources", id);
+            id
+        }
+    };
+    let entries = match doc.get_dictionary(resources_id)?.get(category.as_bytes()) {
+        Ok(Object::Reference(id)) => doc.get_dictionary_mut(*id)?,
+        existing => {
+            let entries = existing
+                .and_then(Object::as_dict)
+                .cloned()
+                .unwrap_or_default();
+            let resources = doc.get_dictionary_mut(resources_id)?;
+            resources.set(category, entries);
+            resources.get_mut(category.as_bytes())?.as_dict_mut()?
+        }
+    };
+
+    if let Some((name, _)) = entries
+        .iter()
+        .find(|(name, entry)| name.starts_with(prefix.as_bytes()) && **entry == value)
+    {
+        return Ok(String::from_utf8_lossy(name).into_owned());
+    }
+    let name = (0..)
+        .map(|n| {
+            if n == 0 {
+                prefix.to_string()
+            } else {
+                format!("{}{}", prefix, n)
+            }
+        })
+        .find(|name| !entries.has(name.as_bytes()))
+        .unwrap_or_default();
+    entries.set(name.as_bytes(), value);
+    Ok(name)
+}
+
+/// Matrix from the displayed page (origin at its lower-left corner, after
+/// /Rotate) to the page's own coordinate space.
+fn page_display_matrix(doc: &Document, page_id: ObjectId) -> [f32; 6] {
+    let [x0, y0, x1, y1] = page_box(doc, page_id, b"CropBox");
+    let (width, height) = (x1 - x0, y1 - y0);
+    match page_rotation(doc, page_id) {
+        90 => [0.0, 1.0, -1.0, 0.0, x0 + width, y0],
+        180 => [-1.0, 0.0, 0.0, -1.0, x0 + width, y0 + height],
+        270 => [0.0, -1.0, 1.0, 0.0, x0, y0 + height],
+        _ => [1.0, 0.0, 0.0, 1.0, x0, y0],
+    }
+}
+
+/// Displayed page size, after /Rotate.
+fn display_size(doc: &Document, page_id: ObjectId) -> (f32, f32) {
+    let [x0, y0, x1, y1] = page_box(doc, page_id, b"CropBox");
+    if page_rotation(doc, page_id) % 180 == 0 {
+        (x1 - x0, y1 - y0)
+    } else {
+        (y1 - y0, x1 - x0)
+    }
+}
+
+/// Lower-left corner for a `width` x `height` box placed at `position` on a
+/// `page` sized area, keeping `margin` from the edges.
+fn anchor_position(
+    position: &str,
+    (width, height): (f32, f32),
+    (page_width, page_height): (f32, f32),
+    margin: f32,
+) -> Result<(f32, f32)> {
+    let (vertical, horizontal) = match position {
+        "center" => ("center", "center"),
+        "top" | "bottom" => (position, "center"),
+        "left" | "right" => ("center", position),
+        _ => match position.split_once('-') {
+            Some((vertical @ ("top" | "bottom"), horizontal @ ("left" | "right"))) => {
+                (vertical, horizontal)
+            }
+            _ => bail!(
+                "Unknown position: '{}' (use center, top, bottom, left, right, top-left, top-right, bottom-left or bottom-right)",
+                position
+            ),
+        },
+    };
+    let x = match horizontal {
+        "left" => margin,
+        "right" => page_width - margin - width,
+        _ => (page_width - width) / 2.0,
+    };
+    let y = match vertical {
+        "bottom" => margin,
+        "top" => page_height - margin - height,
+        _ => (page_height - height) / 2.0,
+    };
+    Ok((x, y))
+}
+
+fn matrix_op(matrix: [f32; 6]) -> String {
+    let [a, b, c, d, e, f] = matrix;
+    format!("{} {} {} {} {} {} cm\n", a, b, c, d, e, f)
+}
+
+/// Loads a PNG or JPEG as an image XObject, keeping transparency as a soft mask.
+fn load_image_xobject(doc: &mut Document, path: &Path) -> Result<(ObjectId, (u32, u32))> {
+    let image =
+        image::open(path).with_context(|| format!("Failed to read image: {}", path.display()))?;
+    let (width, height) = (image.width(), image.height());
+
+    let image_stream = |color_space: &[u8], data: Vec<u8>| {
+        let mut dict = Dictionary::new();
+        dict.set("Type", Object::Name(b"XObject".to_vec()));
+        dict.set("Subtype", Object::Name(b"Image".to_vec()));
+        dict.set("Width", Object::Integer(width as i64));
+        dict.set("Height", Object::Integer(height as i64));
+        dict.set("ColorSpace", Object::Name(color_space.to_vec()));
+        dict.set("BitsPerComponent", Object::Integer(8));
+        let mut stream = Stream::new(dict, data);
+        let _ = stream.compress();
+        stream
+    };
+
+    let mut stream = image_stream(b"DeviceRGB", image.to_rgb8().into_raw());
+    if image.color().has_alpha() {
+        let alpha = image.to_rgba8().pixels().map(|pixel| pixel[3]).collect();
+        let mask_id = doc.add_object(image_stream(b"DeviceGray", alpha));
+        stream.dict.set("SMask", Object::Reference(mask_id));
+    }
+    Ok((doc.add_object(stream), (width, height)))
+}
+
+enum StampSource {
+    Text(String),
+    Image(PathBuf),
+    Pdf(PathBuf, usize),
+}
+
+struct StampOptions {
+    source: StampSource,
+    underlay: bool,
+    opacity: f32,
+    rotation: f32,
+    position: String,
+    margin: f32,
+    font: &'static str,
+    font_size: f32,
+    color: [f32; 3],
+    width: Option<f32>,
+}
+
+fn stamp_pdf(
+    input: &Path,
+    output: &Path,
+    pages_spec: Option<&str>,
+    options: &StampOptions,
+    password: Option<&str>,
+) -> Result<()> {
+    if !(0.0..=1.0).contains(&options.opacity) {
+        bail!("Opacity must be between 0 and 1 (got {})", options.opacity);
+    }
+    anchor_position(&options.position, (0.0, 0.0), (0.0, 0.0), 0.0)?;
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let page_numbers = match pages_spec {
+        Some(spec) => parse_page_ranges(spec, total_pages)?,
+        None => (1..=total_pages).collect(),
+    };
+    if page_numbers.is_empty() {
+        bail!("No pages to stamp (check your page range)");
+    }
+
+    // Resources shared by every stamped page, and the stamp's own size and
+    // drawing operators in its local space.
+    let (category, resource, size, draw) = match &options.source {
+        StampSource::Text(text) => {
+            let font_id = doc.add_object(standard_font(options.font));
+            let width = text_width(options.font, text, options.font_size);
+            let draw = draw_text(
+                "{name}",
+                options.font_size,
+                options.color,
+                0.0,
+                options.font_size * 0.22,
+                text,
+            );
+            ("Font", font_id, (width, options.font_size), draw)
+        }
+        StampSource::Image(path) => {
+            let (image_id, (pixels_wide, pixels_high)) = load_image_xobject(&mut doc, path)?;
+            let width = options.width.unwrap_or(pixels_wide as f32);
+            let height = width * pixels_high as f32 / pixels_wide as f32;
+            let draw = format!("{} 0 0 {} 0 0 cm /{{name}} Do\n", width, height);
+            ("XObject", image_id, (width, height), draw)
+        }
+        StampSource::Pdf(path, page_num) => {
+            let source = load_pdf(path, password)?;
+            let source_pages = source.get_pages();
+            let Some(&source_page) = source_pages.get(&(*page_num as u32)) else {
+                bail!(
+                    "Page {} does not exist in {} ({} pages)",
+                    page_num,
+                    path.display(),
+                    source_pages.len()
+                );
+            };
+            let id_map = import_objects(&mut doc, &source)?;
+            let form = page_to_form(&mut doc, id_map[&source_page])?;
+            let (form_width, form_height) = form_size(&form);
+            let scale = options.width.map_or(1.0, |width| width / form_width);
+            let draw = format!(
+                "{}/{{name}} Do\n",
+                matrix_op(form_matrix(&form, scale, 0.0, 0.0))
+            );
+            (
+                "XObject",
+                form.id,
+                (form_width * scale, form_height * scale),
+                draw,
+            )
+        }
+    };
+
+    let graphics_state = (options.opacity < 1.0).then(|| {
+        let mut dict = Dictionary::new();
+        dict.set("Type", Object::Name(b"ExtGState".to_vec()));
+        dict.set("ca", Object::Real(options.opacity));
+        dict.set("CA", Object::Real(options.opacity));
+        doc.add_object(dict)
+    });
+
+    let (radians_sin, radians_cos) = options.rotation.to_radians().sin_cos();
+    let (width, height) = size;
+    let bounds = (
+        (width * radians_cos).abs() + (height * radians_sin).abs(),
+        (width * radians_sin).abs() + (height * radians_cos).abs(),
+    );
+
+    for &page_num in &page_numbers {
+        let page_id = pages[&(page_num as u32)];
+        let (x, y) = anchor_position(
+            &options.position,
+            bounds,
+            display_size(&doc, page_id),
+            options.margin,
+        )?;
+
+        let name = add_page_resource(
+            &mut doc,
+            page_id,
+            category,
+            "PdferStamp",
+            Object::Reference(resource),
+        )?;
+        let mut stamp = String::from("q\n");
+        if let Some(state_id) = graphics_state {
+            let state = add_page_resource(
+                &mut doc,
+                page_id,
+                "ExtGState",
+                "PdferStampGS",
+                Object::Reference(state_id),
+            )?;
+            stamp.push_str(&format!("/{} gs\n", state));
+        }
+        stamp.push_str(&matrix_op(page_display_matrix(&doc, page_id)));
+        stamp.push_str(&matrix_op([
+            1.0,
+            0.0,
+            0.0,
+            1.0,
+            x + bounds.0 / 2.0,
+            y + bounds.1 / 2.0,
+        ]));
+        if options.rotation != 0.0 {
+            stamp.push_str(&matrix_op([
+                radians_cos,
+                radians_sin,
+                -radians_sin,
+                radians_cos,
+                0.0,
+                0.0,
+            ]));
+        }
+        stamp.push_str(&matrix_op([
+            1.0,
+            0.0,
+            0.0,
+            1.0,
+            -width / 2.0,
+            -height / 2.0,
+        ]));
+        stamp.push_str(&draw.replace("{name}", &name));
+        stamp.push_str("Q\n");
+
+        if options.underlay {
+            wrap_page_content(&mut doc, page_id, stamp.into_bytes(), Vec::new())?;
+        } else {
+            let mut after = b"Q\n".to_vec();
+            after.extend(stamp.into_bytes());
+            wrap_page_content(&mut doc, page_id, b"q\n".to_vec(), after)?;
+        }
+    }
+
+    if matches!(options.source, StampSource::Pdf(..)) {
+        prune_unreachable_objects(&mut doc);
+    }
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Stamped {} page(s){}: {}",
+        page_numbers.len(),
+        if options.underlay { " (underlay)" } else { "" },
+        current_output.display()
+    );
+    Ok(())
+}
+
+/// Which overlay page goes onto each page of the target document.
+enum OverlayMapping {
+    Match,
+    Repeat,
+    Cycle,
+    Explicit(String),
+}
+
+/// Resolves the mapping into overlay page numbers, indexed by target page
+/// number minus one.
+fn overlay_page_numbers(
+    mapping: &OverlayMapping,
+    total_pages: usize,
+    overlay_pages: usize,
+) -> Result<Vec<Option<usize>>> {
+    Ok(match mapping {
+        OverlayMapping::Match => (1..=total_pages)
+            .map(|page| (page <= overlay_pages).then_some(page))
+            .collect(),
+        OverlayMapping::Repeat => vec![Some(1); total_pages],
+        OverlayMapping::Cycle => (0..total_pages)
+            .map(|index| Some(index % overlay_pages + 1))
+            .collect(),
+        OverlayMapping::Explicit(spec) => {
+            let mut mapped = vec![None; total_pages];
+            for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let Some((pages, overlay_page)) = entry.split_once(':') else {
+                    bail!(
+                        "Invalid mapping: '{}' (expected PAGES:N, e.g. 1:1,2-:2)",
+                        entry
+                    );
+                };
+                let overlay_page: usize = overlay_page
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid overlay page in '{}'", entry))?;
+                if overlay_page < 1 || overlay_page > overlay_pages {
+                    bail!(
+                        "Overlay page {} does not exist ({} pages)",
+                        overlay_page,
+                        overlay_pages
+                    );
+                }
+                for page in parse_page_ranges(pages, total_pages)? {
+                    mapped[page - 1] = Some(overlay_page);
+                }
+            }
+            mapped
+        }
+    })
+}
+
+/// Imports every page of `path` into `doc` as Form XObjects.
+fn import_page_forms(
+    doc: &mut Document,
+    path: &Path,
+    password: Option<&str>,
+) -> Result<Vec<PageForm>> {
+    let source = load_pdf(path, password)?;
+    let source_pages = source.get_pages();
+    if source_pages.is_empty() {
+        bail!("PDF has no pages: {}", path.display());
+    }
+    let id_map = import_objects(doc, &source)?;
+    source_pages
+        .values()
+        .map(|page_id| page_to_form(doc, id_map[page_id]))
+        .collect()
+}
+
+fn overlay_pdf(
+    input: &Path,
+    output: &Path,
+    background: Option<&Path>,
+    foreground: Option<&Path>,
+    mapping: &OverlayMapping,
+    password: Option<&str>,
+) -> Result<()> {
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let mut layers = Vec::new();
+    for (path, is_background) in [(background, true), (foreground, false)] {
+        if let Some(path) = path {
+            let forms = import_page_forms(&mut doc, path, password)?;
+            let numbers = overlay_page_numbers(mapping, total_pages, forms.len())?;
+            layers.push((forms, numbers, is_background));
+        }
+    }
+
+    let mut overlaid = 0;
+    for (&page_num, &page_id) in &pages {
+        let display = page_display_matrix(&doc, page_id);
+        let (width, height) = display_size(&doc, page_id);
+        let mut before = String::new();
+        let mut after = String::new();
+
+        for (forms, numbers, is_background) in &layers {
+            let Some(overlay_page) = numbers[page_num as usize - 1] else {
+                continue;
+            };
+            let form = &forms[overlay_page - 1];
+            let name = add_page_resource(
+                &mut doc,
+                page_id,
+                "XObject",
+                "PdferOverlay",
+                Object::Reference(form.id),
+            )?;
+            let (matrix, _) = fit_form(form, [0.0, 0.0, width, height]);
+            let layer = format!("q\n{}{}Q\n", matrix_op(display), draw_form(&name, matrix));
+            if *is_background {
+                before.push_str(&layer);
+            } else {
+                after.push_str(&layer);
+            }
+        }
+        if before.is_empty() && after.is_empty() {
+            continue;
+        }
+
+        if !after.is_empty() {
+            before.push_str("q\n");
+            after.insert_str(0, "Q\n");
+        }
+        wrap_page_content(&mut doc, page_id, before.into_bytes(), after.into_bytes())?;
+        overlaid += 1;
+    }
+    prune_unreachable_objects(&mut doc);
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Overlaid {} of {} page(s): {}",
+        overlaid,
+        total_pages,
+        current_output.display()
+    );
+    Ok(())
+}
+
+fn to_roman(mut value: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut roman = String::new();
+    for (amount, numeral) in NUMERALS {
+        while value >= amount {
+            roman.push_str(numeral);
+            value -= amount;
+        }
+    }
+    roman
+}
+
+/// Formats `value` in a /PageLabels numbering style (D, R, r, A or a).
+fn format_label_number(style: Option<&[u8]>, value: u32) -> String {
+    match style {
+        Some(b"D") => value.to_string(),
+        Some(b"R") => to_roman(value),
+        Some(b"r") => to_roman(value).to_lowercase(),
+        Some(style @ (b"A" | b"a")) if value > 0 => {
+            let base = if style == b"A" { b'A' } else { b'a' };
+            let letter = (base + ((value - 1) % 26) as u8) as char;
+            letter.to_string().repeat(((value - 1) / 26 + 1) as usize)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Collects the dictionaries of a number tree such as /PageLabels.
+/// Indirect entries inside each dictionary (e.g. a label's /P prefix) are
+/// resolved so callers can read them without the source document.
+fn collect_number_tree(doc: &Document, node: &Object, entries: &mut BTreeMap<i64, Dictionary>) {
+    let Some(node) = doc
+        .dereference(node)
+        .ok()
+        .and_then(|(_, node)| node.as_dict().ok())
+    else {
+        return;
+    };
+    if let Ok(nums) = node.get(b"Nums").and_then(Object::as_array) {
+        for pair in nums.chunks(2) {
+            if let [Object::Integer(key), value] = pair
+                && let Ok((_, Object::Dictionary(dict))) = doc.dereference(value)
+            {
+                let mut dict = dict.clone();
+                for (_, value) in dict.iter_mut() {
+                    if let Object::Reference(id) = value
+                        && let Ok(resolved) = doc.get_object(*id)
+                    {
+                        *value = resolved.clone();
+                    }
+                }
+                entries.insert(*key, dict);
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+        for kid in kids {
+            collect_number_tree(doc, kid, entries);
+        }
+    }
+}
+
+/// Page labels from the catalog's /PageLabels tree, one per page, or None
+/// when the document defines no labels.
+fn page_labels(doc: &Document) -> Option<Vec<String>> {
+    let tree = doc.catalog().ok()?.get(b"PageLabels").ok()?;
+    let mut ranges = BTreeMap::new();
+    collect_number_tree(doc, tree, &mut ranges);
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let total_pages = doc.get_pages().len() as i64;
+    let labels = (0..total_pages)
+        .map(|index| {
+            let Some((&first, range)) = ranges.range(..=index).next_back() else {
+                return (index + 1).to_string();
+            };
+            let prefix = range
+                .get(b"P")
+                .ok()
+                .and_then(|prefix| decode_text_string(prefix).ok())
+                .unwrap_or_default();
+            let start = range.get(b"St").and_then(Object::as_i64).unwrap_or(1);
+            let style = range.get(b"S").and_then(Object::as_name).ok();
+            let number = format_label_number(style, (start + index - first).max(0) as u32);
+            format!("{}{}", prefix, number)
+        })
+        .collect();
+    Some(labels)
+}
+
+/// Font, size and placement shared by text drawn into page margins.
+struct TextStyle {
+    font: &'static str,
+    font_id: ObjectId,
+    size: f32,
+    color: [f32; 3],
+    margin: f32,
+}
+
+fn draw_text(resource: &str, size: f32, [r, g, b]: [f32; 3], x: f32, y: f32, text: &str) -> String {
+    format!(
+        "BT /{} {} Tf {} {} {} rg {} {} Td {} Tj ET\n",
+        resource,
+        size,
+        r,
+        g,
+        b,
+        x,
+        y,
+        pdf_text(text)
+    )
+}
+
+/// Draws each `(position, text)` pair on top of the page, upright as the
+/// page is displayed.
+fn add_page_text(
+    doc: &mut Document,
+    page_id: ObjectId,
+    style: &TextStyle,
+    items: &[(String, String)],
+) -> Result<()> {
+    let font = add_page_resource(
+        doc,
+        page_id,
+        "Font",
+        "PdferText",
+        Object::Reference(style.font_id),
+    )?;
+    let page_size = display_size(doc, page_id);
+
+    let mut after = format!("Q\nq\n{}", matrix_op(page_display_matrix(doc, page_id)));
+    for (position, text) in items {
+        let width = text_width(style.font, text, style.size);
+        let (x, y) = anchor_position(position, (width, style.size), page_size, style.margin)?;
+        after.push_str(&draw_text(
+            &font,
+            style.size,
+            style.color,
+            x,
+            y + style.size * 0.22,
+            text,
+        ));
+    }
+    after.push_str("Q\n");
+    wrap_page_content(doc, page_id, b"q\n".to_vec(), after.into_bytes())
+}
+
+/// Position name for a header or footer with the given alignment.
+fn margin_position(vertical: &str, align: &str) -> Result<String> {
+    Ok(match align {
+        "center" => vertical.to_string(),
+        "left" | "right" => format!("{}-{}", vertical, align),
+        _ => bail!("Unknown alignment: '{}' (use left, center or right)", align),
+    })
+}
+
+struct NumberOptions {
+    header: Option<String>,
+    footer: Option<String>,
+    align: String,
+    start: i64,
+    skip: Option<String>,
+    font: &'static str,
+    font_size: f32,
+    color: [f32; 3],
+    margin: f32,
+}
+
+fn number_pdf(
+    input: &Path,
+    output: &Path,
+    options: &NumberOptions,
+    password: Option<&str>,
+) -> Result<()> {
+    margin_position("top", &options.align)?;
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+
+    let mut doc = load_pdf(input, password)?;
+    let pages = doc.get_pages();
+    let total_pages = pages.len();
+    println!("PDF has {} pages.", total_pages);
+
+    let skipped: BTreeSet<usize> = match &options.skip {
+        Some(spec) => parse_page_ranges(spec, total_pages)?.into_iter().collect(),
+        None => BTreeSet::new(),
+    };
+    let labels = page_labels(&doc);
+    let filename = input
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let date = Local::now().format("%Y-%m-%d").to_string();
+    let total = (total_pages as i64 + options.start - 1).to_string();
+
+    let style = TextStyle {
+        font: options.font,
+        font_id: doc.add_object(standard_font(options.font)),
+        size: options.font_size,
+        color: options.color,
+        margin: options.margin,
+    };
+    let mut numbered = 0;
+    for (&page_num, &page_id) in &pages {
+        if skipped.contains(&(page_num as usize)) {
+            continue;
+        }
+        let number = (page_num as i64 + options.start - 1).to_string();
+        let label = labels.as_ref().map_or(number.clone(), |labels| {
+            labels[page_num as usize - 1].clone()
+        });
+        let fill = |template: &str| {
+            template
+                .replace("{page}", &number)
+                .replace("{total}", &total)
+                .replace("{label}", &label)
+                .replace("{filename}", &filename)
+                .replace("{date}", &date)
+        };
+
+        let mut items = Vec::new();
+        if let Some(header) = &options.header {
+            items.push((margin_position("top", &options.align)?, fill(header)));
+        }
+        if let Some(footer) = &options.footer {
+            items.push((margin_position("bottom", &options.align)?, fill(footer)));
+        }
+        add_page_text(&mut doc, page_id, &style, &items)?;
+        numbered += 1;
+    }
+
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Numbered {} of {} page(s): {}",
+        numbered,
+        total_pages,
+        current_output.display()
+    );
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Splits CSV text into records, undoing `csv_field`'s quoting: quoted
+/// fields may hold commas, doubled quotes and line breaks.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            '\r' if !quoted => {}
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|record| record.iter().any(|field| !field.trim().is_empty()));
+    records
+}
+
+/// Reads the counter to continue from: one past the last Bates number
+/// recorded in a previous run's log.
+fn next_bates_number(log: &Path, prefix: &str, suffix: &str) -> Result<u64> {
+    let contents = std::fs::read_to_string(log)
+        .with_context(|| format!("Failed to read Bates log: {}", log.display()))?;
+    let records = parse_csv(&contents);
+    let Some(last) = records
+        .iter()
+        .skip(1)
+        .last()
+        .and_then(|record| record.last())
+    else {
+        bail!("Bates log has no entries: {}", log.display());
+    };
+    let number = last
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.strip_suffix(suffix))
+        .and_then(|digits| digits.parse::<u64>().ok())
+        .with_context(|| {
+            format!(
+                "Cannot read a Bates number from '{}' with prefix '{}' and suffix '{}'",
+                last, prefix, suffix
+            )
+        })?;
+    Ok(number + 1)
+}
+
+struct BatesOptions {
+    prefix: String,
+    suffix: String,
+    digits: usize,
+    start: u64,
+    position: String,
+    font: &'static str,
+    font_size: f32,
+    color: [f32; 3],
+    margin: f32,
+}
+
+/// Stamps `inputs` (found under `roots`) into `output_dir`, mirroring each
+/// file's path relative to the directory it was found in.
+fn bates_pdfs(
+    inputs: &[PathBuf],
+    roots: &[PathBuf],
+    output_dir: &Path,
+    log: Option<&Path>,
+    options: &BatesOptions,
+    password: Option<&str>,
+) -> Result<()> {
+    anchor_position(&options.position, (0.0, 0.0), (0.0, 0.0), 0.0)?;
+    if inputs.is_empty() {
+        bail!("No PDF files found");
+    }
+
+    let relative_paths: Vec<PathBuf> = inputs
+        .iter()
+        .map(|input| {
+            roots
+                .iter()
+                .filter(|root| root.is_dir())
+                .find_map(|root| input.strip_prefix(root).ok())
+                .map_or_else(
+                    || PathBuf::from(input.file_name().unwrap_or_default()),
+                    Path::to_path_buf,
+                )
+        })
+        .collect();
+    let mut seen = HashSet::new();
+    for path in &relative_paths {
+        if !seen.insert(path) {
+            bail!(
+                "Two inputs map to the same output '{}'; Bates outputs would collide",
+                path.display()
+            );
+        }
+    }
+
+    let Some(output_dir) = resolve_output_conflict(output_dir, true)? else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(&output_dir).with_context(|| {
+        format!(
+            "Failed to create output directory: {}",
+            output_dir.display()
+        )
+    })?;
+
+    let label = |number: u64| {
+        format!(
+            "{}{:0width$}{}",
+            options.prefix,
+            number,
+            options.suffix,
+            width = options.digits
+        )
+    };
+
+    println!("Bates numbering {} PDF(s)...", inputs.len());
+    let mut rows = vec!["file,pages,first,last".to_string()];
+    let mut number = options.start;
+    for (input, relative_path) in inputs.iter().zip(&relative_paths) {
+        let mut doc = load_pdf(input, password)?;
+        let pages = doc.get_pages();
+        if pages.is_empty() {
+            bail!("PDF has no pages: {}", input.display());
+        }
+
+        let style = TextStyle {
+            font: options.font,
+            font_id: doc.add_object(standard_font(options.font)),
+            size: options.font_size,
+            color: options.color,
+            margin: options.margin,
+        };
+        let first = number;
+        for &page_id in pages.values() {
+            add_page_text(
+                &mut doc,
+                page_id,
+                &style,
+                &[(options.position.clone(), label(number))],
+            )?;
+            number += 1;
+        }
+
+        let output = output_dir.join(relative_path);
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create output directory: {}", parent.display())
+            })?;
+        }
+        doc.save(&output)
+            .with_context(|| format!("Failed to save: {}", output.display()))?;
+        println!(
+            "  {}: {} – {}",
+            input.display(),
+            label(first),
+            label(number - 1)
+        );
+        rows.push(format!(
+            "{},{},{},{}",
+            csv_field(&input.display().to_string()),
+            pages.len(),
+            csv_field(&label(first)),
+            csv_field(&label(number - 1))
+        ));
+    }
+
+    let log = log.map_or_else(|| output_dir.join("bates_log.csv"), Path::to_path_buf);
+    std::fs::write(&log, rows.join("\n") + "\n")
+        .with_context(|| format!("Failed to write Bates log: {}", log.display()))?;
+    println!("Log written: {}", log.display());
+    println!(
+        "✓ Stamped {} page(s), {} – {}: {}",
+        number - options.start,
+        label(options.start),
+        label(number - 1),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+/// One bookmark in document order; `level` 0 is top-level.
+struct OutlineEntry {
+    title: String,
+    page: Option<u32>,
+    /// Destination type and its parameters, e.g. `/XYZ 72 700 null`;
+    /// empty means `/Fit`.
+    view: Vec<Object>,
+    /// Target of a URI action, for bookmarks that open a link instead.
+    uri: Option<String>,
+    level: usize,
+    bold: bool,
+    italic: bool,
+    color: Option<[f32; 3]>,
+    open: bool,
+}
+
+fn read_outline(doc: &Document) -> Vec<OutlineEntry> {
+    fn visit(
+        doc: &Document,
+        first: Option<ObjectId>,
+        level: usize,
+        page_numbers: &HashMap<ObjectId, u32>,
+        visited: &mut HashSet<ObjectId>,
+        entries: &mut Vec<OutlineEntry>,
+    ) {
+        let mut item = first;
+        while let Some(item_id) = item {
+            if !visited.insert(item_id) {
+                break;
+            }
+            let Ok(entry) = doc.get_dictionary(item_id) else {
+                break;
+            };
+            let flags = entry.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+            let target = entry.get(b"Dest").or_else(|_| entry.get(b"A")).ok();
+            let view = target
+                .and_then(|target| resolve_destination(doc, target))
+                .map(|dest| dest.get(1..).unwrap_or_default().to_vec())
+                .filter(|view| !matches!(view.as_slice(), [Object::Name(name)] if name == b"Fit"))
+                .unwrap_or_default();
+            let uri = entry
+                .get(b"A")
+                .ok()
+                .and_then(|action| doc.dereference(action).ok())
+                .and_then(|(_, action)| action.as_dict().ok())
+                .filter(
+                    |action| matches!(action.get(b"S"), Ok(Object::Name(name)) if name == b"URI"),
+                )
+                .and_then(|action| action.get(b"URI").ok())
+                .and_then(|uri| doc.dereference(uri).ok())
+                .and_then(|(_, uri)| uri.as_str().ok())
+                .map(|uri| String::from_utf8_lossy(uri).into_owned());
+            let color = entry
+                .get(b"C")
+                .and_then(Object::as_array)
+                .ok()
+                .map(|items| items.iter().filter_map(object_as_f32).collect::<Vec<_>>());
+            entries.push(OutlineEntry {
+                title: entry
+                    .get(b"Title")
+                    .ok()
+                    .and_then(|title| doc.dereference(title).ok())
+                    .and_then(|(_, title)| decode_text_string(title).ok())
+                    .unwrap_or_default(),
+                page: target.and_then(|dest| resolve_destination_page(doc, dest, page_numbers)),
+                view,
+                uri,
+                level,
+                bold: flags & 2 != 0,
+                italic: flags & 1 != 0,
+                color: match color.as_deref() {
+                    Some(&[r, g, b]) if [r, g, b] != [0.0, 0.0, 0.0] => Some([r, g, b]),
+                    _ => None,
+                },
+                open: entry.get(b"Count").and_then(Object::as_i64).unwrap_or(0) > 0,
+            });
+            let child = entry.get(b"First").and_then(Object::as_reference).ok();
+            visit(doc, child, level + 1, page_numbers, visited, entries);
+            item = entry.get(b"Next").and_then(Object::as_reference).ok();
+        }
+    }
+
+    let page_numbers: HashMap<ObjectId, u32> = doc
+        .get_pages()
+        .into_iter()
+        .map(|(num, id)| (id, num))
+        .collect();
+    let first = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|outlines| doc.dereference(outlines).ok())
+        .and_then(|(_, outlines)| outlines.as_dict().ok())
+        .and_then(|outlines| outlines.get(b"First").ok())
+        .and_then(|first| first.as_reference().ok());
+
+    let mut entries = Vec::new();
+    visit(
+        doc,
+        first,
+        0,
+        &page_numbers,
+        &mut HashSet::new(),
+        &mut entries,
+    );
+    entries
+}
+
+/// Replaces the document outline with `entries`, linking /Parent, /First,
+/// /Last, /Prev, /Next and /Count from their levels. With `append` the
+/// entries are linked after the existing top-level /Last instead, leaving
+/// the current bookmarks untouched.
+fn write_outline(doc: &mut Document, entries: &[OutlineEntry], append: bool) -> Result<()> {
+    if let Some(first) = entries.first()
+        && first.level != 0
+    {
+        bail!("The first bookmark must be at level 0");
+    }
+    if let Some(pair) = entries
+        .windows(2)
+        .find(|pair| pair[1].level > pair[0].level + 1)
+    {
+        bail!(
+            "Bookmark '{}' skips a level (from {} to {})",
+            pair[1].title,
+            pair[0].level,
+            pair[1].level
+        );
+    }
+
+    let pages = doc.get_pages();
+    let existing = match doc.catalog()?.get(b"Outlines") {
+        Ok(Object::Reference(id)) if append && doc.get_dictionary(*id).is_ok() => Some(*id),
+        _ => None,
+    };
+    let previous_last = existing
+        .and_then(|id| doc.get_dictionary(id).ok())
+        .and_then(|outlines| outlines.get(b"Last").ok())
+        .and_then(|last| last.as_reference().ok())
+        .filter(|&last| doc.get_dictionary(last).is_ok());
+    let outlines_id = existing.unwrap_or_else(|| doc.new_object_id());
+    let ids: Vec<ObjectId> = entries.iter().map(|_| doc.new_object_id()).collect();
+
+    // Parent index for every entry (None for top level) and children per parent.
+    let mut parents: Vec<Option<usize>> = Vec::with_capacity(entries.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        stack.truncate(entry.level);
+        parents.push(stack.last().copied());
+        stack.push(index);
+    }
+    let children = |parent: Option<usize>| -> Vec<usize> {
+        (0..entries.len())
+            .filter(|&index| parents[index] == parent)
+            .collect()
+    };
+
+    // Visible descendants when the entry is open, computed bottom-up.
+    let mut visible = vec![0i64; entries.len()];
+    for index in (0..entries.len()).rev() {
+        if let Some(parent) = parents[index] {
+            visible[parent] += 1 + if entries[index].open {
+                visible[index]
+            } else {
+                0
+            };
+        }
+    }
+
+    for (index, entry) in entries.iter().enumerate() {
+        let mut item = Dictionary::new();
+        item.set("Title", text_string(&entry.title));
+        item.set(
+            "Parent",
+            Object::Reference(parents[index].map_or(outlines_id, |parent| ids[parent])),
+        );
+        if let Some(page) = entry.page {
+            let Some(&page_id) = pages.get(&page) else {
                 bail!(
-                    "Page {} is out of range (PDF has {} pages)",
+                    "Bookmark '{}' points to page {}, but the document has {} pages",
+                    entry.title,
                     page,
-                    total_pages
+                    pages.len()
                 );
+            };
+            let mut dest = vec![Object::Reference(page_id)];
+            if entry.view.is_empty() {
+                dest.push(Object::Name(b"Fit".to_vec()));
+            } else {
+                dest.extend(entry.view.iter().cloned());
             }
-            pages.push(page);
+            item.set("Dest", Object::Array(dest));
+        } else if let Some(uri) = &entry.uri {
+            let mut action = Dictionary::new();
+            action.set("S", Object::Name(b"URI".to_vec()));
+            action.set("URI", Object::string_literal(uri.as_str()));
+            item.set("A", Object::Dictionary(action));
+        }
+
+        let siblings = children(parents[index]);
+        let position = siblings.iter().position(|&i| i == index).unwrap_or(0);
+        if position > 0 {
+            item.set("Prev", Object::Reference(ids[siblings[position - 1]]));
+        } else if let (None, Some(last)) = (parents[index], previous_last) {
+            item.set("Prev", Object::Reference(last));
+        }
+        if let Some(&next) = siblings.get(position + 1) {
+            item.set("Next", Object::Reference(ids[next]));
+        }
+
+        let kids = children(Some(index));
+        if let (Some(&first), Some(&last)) = (kids.first(), kids.last()) {
+            item.set("First", Object::Reference(ids[first]));
+            item.set("Last", Object::Reference(ids[last]));
+            let count = if entry.open {
+                visible[index]
+            } else {
+                -visible[index]
+            };
+            item.set("Count", Object::Integer(count));
+        }
+
+        let flags = i64::from(entry.italic) | (i64::from(entry.bold) << 1);
+        if flags != 0 {
+            item.set("F", Object::Integer(flags));
+        }
+        if let Some(color) = entry.color {
+            item.set(
+                "C",
+                Object::Array(color.iter().map(|&c| Object::Real(c)).collect()),
+            );
+        }
+        doc.objects.insert(ids[index], Object::Dictionary(item));
+    }
+
+    let top_level = children(None);
+    let mut outlines = match existing {
+        Some(id) => doc.get_dictionary(id)?.clone(),
+        None => Dictionary::new(),
+    };
+    outlines.set("Type", Object::Name(b"Outlines".to_vec()));
+    if let (Some(&first), Some(&last)) = (top_level.first(), top_level.last()) {
+        match previous_last {
+            Some(previous_last) => {
+                doc.get_dictionary_mut(previous_last)?
+                    .set("Next", Object::Reference(ids[first]));
+            }
+            None => outlines.set("First", Object::Reference(ids[first])),
+        }
+        outlines.set("Last", Object::Reference(ids[last]));
+        let count: i64 = top_level
+            .iter()
+            .map(|&index| {
+                1 + if entries[index].open {
+                    visible[index]
+                } else {
+                    0
+                }
+            })
+            .sum();
+        let current = match previous_last {
+            Some(_) => outlines.get(b"Count").and_then(Object::as_i64).unwrap_or(0),
+            None => 0,
+        };
+        outlines.set("Count", Object::Integer(current.abs() + count));
+    }
+    doc.objects
+        .insert(outlines_id, Object::Dictionary(outlines));
+
+    let catalog = doc.catalog_mut()?;
+    catalog.set("Outlines", Object::Reference(outlines_id));
+    if !entries.is_empty() {
+        catalog.set("PageMode", Object::Name(b"UseOutlines".to_vec()));
+    }
+    Ok(())
+}
+
+fn format_color(color: [f32; 3]) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02X}{:02X}{:02X}",
+        channel(color[0]),
+        channel(color[1]),
+        channel(color[2])
+    )
+}
+
+/// Destination view as words, e.g. `XYZ 72 700 null`.
+fn view_words(view: &[Object]) -> Vec<String> {
+    view.iter()
+        .map(|item| match item {
+            Object::Name(name) => String::from_utf8_lossy(name).into_owned(),
+            Object::Integer(value) => value.to_string(),
+            Object::Real(value) => value.to_string(),
+            _ => "null".to_string(),
+        })
+        .collect()
+}
+
+/// Validates a destination view such as `XYZ 72 700 null` and returns the
+/// objects that follow the page in a /Dest array; `Fit` gives an empty view.
+fn parse_view(words: &[&str]) -> Result<Vec<Object>> {
+    let Some((&kind, values)) = words.split_first() else {
+        return Ok(Vec::new());
+    };
+    let expected = match kind {
+        "Fit" | "FitB" => 0,
+        "FitH" | "FitV" | "FitBH" | "FitBV" => 1,
+        "XYZ" => 3,
+        "FitR" => 4,
+        _ => bail!(
+            "Unknown destination '{}' (expected XYZ, Fit, FitH, FitV, FitR, FitB, FitBH or FitBV)",
+            kind
+        ),
+    };
+    if values.len() != expected {
+        bail!(
+            "Destination {} takes {} value(s), got {}",
+            kind,
+            expected,
+            values.len()
+        );
+    }
+    if kind == "Fit" {
+        return Ok(Vec::new());
+    }
+
+    let mut view = vec![Object::Name(kind.as_bytes().to_vec())];
+    for &value in values {
+        view.push(if value == "null" {
+            Object::Null
+        } else if let Ok(value) = value.parse::<i64>() {
+            Object::Integer(value)
+        } else {
+            Object::Real(
+                value
+                    .parse()
+                    .with_context(|| format!("Invalid destination value '{}'", value))?,
+            )
+        });
+    }
+    Ok(view)
+}
+
+/// Indented text form: `Title | target | style`, two spaces per level.
+fn outline_to_text(entries: &[OutlineEntry]) -> String {
+    let mut text = String::new();
+    for entry in entries {
+        let mut style = Vec::new();
+        if entry.bold {
+            style.push("bold".to_string());
+        }
+        if entry.italic {
+            style.push("italic".to_string());
+        }
+        if let Some(color) = entry.color {
+            style.push(format_color(color));
+        }
+        if entry.open {
+            style.push("open".to_string());
+        }
+
+        text.push_str(&"  ".repeat(entry.level));
+        text.push_str(&entry.title);
+        text.push_str(" | ");
+        let target = match (entry.page, &entry.uri) {
+            (Some(page), _) => {
+                let mut words = vec![page.to_string()];
+                words.extend(view_words(&entry.view));
+                words.join(" ")
+            }
+            (None, Some(uri)) => format!("URI {}", uri),
+            (None, None) => "-".to_string(),
+        };
+        text.push_str(&target);
+        if !style.is_empty() {
+            text.push_str(" | ");
+            text.push_str(&style.join(" "));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn parse_outline_style(entry: &mut OutlineEntry, style: &str) -> Result<()> {
+    for word in style.split_whitespace() {
+        match word.to_ascii_lowercase().as_str() {
+            "bold" => entry.bold = true,
+            "italic" => entry.italic = true,
+            "open" => entry.open = true,
+            "closed" => entry.open = false,
+            _ => entry.color = Some(parse_color(word)?),
+        }
+    }
+    Ok(())
+}
+
+/// Parses a target column: `-`, `PAGE [VIEW]` or `URI address`.
+fn parse_outline_target(entry: &mut OutlineEntry, target: &str) -> Result<()> {
+    let words: Vec<&str> = target.split_whitespace().collect();
+    match words.as_slice() {
+        ["-"] => {}
+        ["URI"] => bail!("URI target needs an address"),
+        ["URI", address @ ..] => entry.uri = Some(address.join(" ")),
+        [page, view @ ..] => {
+            entry.page = Some(
+                page.parse()
+                    .with_context(|| format!("Invalid page '{}'", page))?,
+            );
+            entry.view = parse_view(view)?;
+        }
+        [] => bail!("Empty target"),
+    }
+    Ok(())
+}
+
+fn parse_outline_text(text: &str) -> Result<Vec<OutlineEntry>> {
+    let mut entries = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let indent: usize = line
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { 2 } else { 1 })
+            .sum();
+
+        let mut parts: Vec<&str> = line.trim().split(" | ").collect();
+        let mut entry = OutlineEntry {
+            title: String::new(),
+            page: None,
+            view: Vec::new(),
+            uri: None,
+            level: indent / 2,
+            bold: false,
+            italic: false,
+            color: None,
+            open: false,
+        };
+        let is_target = |part: &str| {
+            let first = part.split_whitespace().next().unwrap_or_default();
+            first == "-" || first == "URI" || first.parse::<u32>().is_ok()
+        };
+        if parts.len() >= 3 && is_target(parts[parts.len() - 2]) {
+            let style = parts.pop().unwrap_or_default();
+            parse_outline_style(&mut entry, style)
+                .with_context(|| format!("Line {}: invalid style '{}'", line_num + 1, style))?;
+        }
+        if parts.len() >= 2 && is_target(parts[parts.len() - 1]) {
+            let target = parts.pop().unwrap_or_default();
+            parse_outline_target(&mut entry, target)
+                .with_context(|| format!("Line {}: invalid target '{}'", line_num + 1, target))?;
+        }
+        entry.title = parts.join(" | ");
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn outline_to_json(entries: &[OutlineEntry]) -> String {
+    let items: Vec<serde_json::Value> = entries
+        .iter()
+        .map(|entry| {
+            serde_json::json!({
+                "title": entry.title,
+                "page": entry.page,
+                "view": (!entry.view.is_empty()).then(|| {
+                    entry.view.iter().map(|item| match item {
+                        Object::Name(name) => serde_json::json!(String::from_utf8_lossy(name)),
+                        Object::Integer(value) => serde_json::json!(value),
+                        Object::Real(value) => serde_json::json!(value),
+                        _ => serde_json::Value::Null,
+                    }).collect::<Vec<_>>()
+                }),
+                "uri": entry.uri,
+                "level": entry.level,
+                "bold": entry.bold,
+                "italic": entry.italic,
+                "color": entry.color.map(format_color),
+                "open": entry.open,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&items).unwrap_or_default()
+}
+
+fn parse_outline_json(data: &str) -> Result<Vec<OutlineEntry>> {
+    let json: serde_json::Value = serde_json::from_str(data).context("Invalid JSON")?;
+    let Some(items) = json.as_array() else {
+        bail!("Outline JSON must be an array of bookmarks");
+    };
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let Some(title) = item.get("title").and_then(|title| title.as_str()) else {
+                bail!("Bookmark {} has no \"title\"", index + 1);
+            };
+            let flag = |key: &str| item.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+            let view: Vec<String> = item
+                .get("view")
+                .and_then(|view| view.as_array())
+                .map(|view| {
+                    view.iter()
+                        .map(|word| match word {
+                            serde_json::Value::String(word) => word.clone(),
+                            word => word.to_string(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            let view: Vec<&str> = view.iter().map(String::as_str).collect();
+            let page = item
+                .get("page")
+                .and_then(|page| page.as_u64())
+                .map(|page| page as u32);
+            let uri = item
+                .get("uri")
+                .and_then(|uri| uri.as_str())
+                .map(str::to_string);
+            if page.is_some() && uri.is_some() {
+                bail!("Bookmark {} has both a \"page\" and a \"uri\"", index + 1);
+            }
+            Ok(OutlineEntry {
+                title: title.to_string(),
+                page,
+                view: parse_view(&view)
+                    .with_context(|| format!("Bookmark {}: invalid \"view\"", index + 1))?,
+                uri,
+                level: item
+                    .get("level")
+                    .and_then(|level| level.as_u64())
+                    .unwrap_or(0) as usize,
+                bold: flag("bold"),
+                italic: flag("italic"),
+                color: item
+                    .get("color")
+                    .and_then(|color| color.as_str())
+                    .map(parse_color)
+                    .transpose()?,
+                open: flag("open"),
+            })
+        })
+        .collect()
+}
+
+fn is_json_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+fn edit_outline(
+    input: &Path,
+    output: &Path,
+    import: Option<&Path>,
+    append: bool,
+    export: Option<&Path>,
+    json: bool,
+    password: Option<&str>,
+) -> Result<()> {
+    let mut doc = load_pdf(input, password)?;
+
+    if let Some(import) = import {
+        let data = std::fs::read_to_string(import)
+            .with_context(|| format!("Failed to read outline file: {}", import.display()))?;
+        let imported = if json || is_json_path(import) {
+            parse_outline_json(&data)
+        } else {
+            parse_outline_text(&data)
+        }
+        .with_context(|| format!("Invalid outline file: {}", import.display()))?;
+
+        let Some(current_output) = prepare_output_file(output)? else {
+            return Ok(());
+        };
+
+        write_outline(&mut doc, &imported, append)?;
+        prune_unreachable_objects(&mut doc);
+
+        doc.save(&current_output)
+            .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+        println!(
+            "✓ {} {} bookmark(s), {} total: {}",
+            if append { "Appended" } else { "Imported" },
+            imported.len(),
+            read_outline(&doc).len(),
+            current_output.display()
+        );
+    } else if export.is_none() {
+        let entries = read_outline(&doc);
+        if entries.is_empty() {
+            println!("No bookmarks in {}", input.display());
+        } else if json {
+            println!("{}", outline_to_json(&entries));
+        } else {
+            print!("{}", outline_to_text(&entries));
+        }
+    }
+
+    if let Some(export) = export {
+        let entries = read_outline(&doc);
+        let data = if json || is_json_path(export) {
+            outline_to_json(&entries) + "\n"
+        } else {
+            outline_to_text(&entries)
+        };
+        if export == Path::new("-") {
+            print!("{}", data);
+        } else {
+            let Some(export_path) = resolve_output_conflict(export, false)? else {
+                return Ok(());
+            };
+            std::fs::write(&export_path, data)
+                .with_context(|| format!("Failed to write: {}", export_path.display()))?;
+            println!(
+                "✓ Exported {} bookmark(s): {}",
+                entries.len(),
+                export_path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Label ranges from the catalog's /PageLabels tree, keyed by 0-based
+/// first page index.
+fn label_ranges(doc: &Document) -> BTreeMap<i64, Dictionary> {
+    let mut ranges = BTreeMap::new();
+    if let Ok(tree) = doc.catalog().and_then(|catalog| catalog.get(b"PageLabels")) {
+        collect_number_tree(doc, tree, &mut ranges);
+    }
+    ranges
+}
+
+fn set_label_ranges(doc: &mut Document, ranges: &BTreeMap<i64, Dictionary>) -> Result<()> {
+    let catalog = doc.catalog_mut()?;
+    if ranges.is_empty() {
+        catalog.remove(b"PageLabels");
+        return Ok(());
+    }
+    let nums = ranges
+        .iter()
+        .flat_map(|(&first, range)| [Object::Integer(first), Object::Dictionary(range.clone())])
+        .collect();
+    let mut tree = Dictionary::new();
+    tree.set("Nums", Object::Array(nums));
+    catalog.set("PageLabels", Object::Dictionary(tree));
+    Ok(())
+}
+
+fn label_range(style: Option<&str>, prefix: &str, start: i64) -> Dictionary {
+    let mut range = Dictionary::new();
+    range.set("Type", Object::Name(b"PageLabel".to_vec()));
+    if let Some(style) = style {
+        range.set("S", Object::Name(style.as_bytes().to_vec()));
+    }
+    if !prefix.is_empty() {
+        range.set("P", text_string(prefix));
+    }
+    if start != 1 {
+        range.set("St", Object::Integer(start));
+    }
+    range
+}
+
+/// The label style (the range dictionary without /St) and number that
+/// page `index` (0-based) takes from `ranges`. Pages not covered by any
+/// range are numbered in plain decimals.
+fn page_label_number(ranges: &BTreeMap<i64, Dictionary>, index: i64) -> (Dictionary, i64) {
+    match ranges.range(..=index).next_back() {
+        Some((&first, range)) => {
+            let start = range.get(b"St").and_then(Object::as_i64).unwrap_or(1);
+            let mut style = range.clone();
+            style.remove(b"St");
+            (style, start + index - first)
+        }
+        None => (label_range(Some("D"), "", 1), index + 1),
+    }
+}
+
+/// Label ranges for pages given in output order as (style, number) pairs,
+/// starting a new range wherever the style changes or the numbering does
+/// not continue from the previous page. `None` marks a page outside any
+/// input (a separator), which gets a range of its own showing its plain
+/// page number.
+fn label_ranges_for_pages(pages: &[Option<(Dictionary, i64)>]) -> BTreeMap<i64, Dictionary> {
+    let mut ranges = BTreeMap::new();
+    let mut previous: Option<&(Dictionary, i64)> = None;
+    for (index, page) in pages.iter().enumerate() {
+        let Some(page) = page else {
+            ranges.insert(index as i64, label_range(Some("D"), "", index as i64 + 1));
+            previous = None;
+            continue;
+        };
+        let (style, number) = page;
+        let continues = previous.is_some_and(|(previous_style, previous_number)| {
+            previous_style == style && previous_number + 1 == *number
+        });
+        if !continues {
+            let mut range = style.clone();
+            if *number != 1 {
+                range.set("St", Object::Integer(*number));
+            }
+            ranges.insert(index as i64, range);
+        }
+        previous = Some(page);
+    }
+    ranges
+}
+
+/// Parses `PAGES:STYLE[:PREFIX[:START]]` entries, e.g. `1-4:r,5-:D`.
+/// Pages left between an entry's end and the next entry keep plain page
+/// numbers.
+fn parse_label_spec(spec: &str, total_pages: usize) -> Result<BTreeMap<i64, Dictionary>> {
+    let mut ranges = BTreeMap::new();
+    let mut next_free = 1;
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let mut fields = entry.splitn(4, ':');
+        let pages = fields.next().unwrap_or_default();
+        let Some(style) = fields.next() else {
+            bail!(
+                "Invalid label range: '{}' (expected PAGES:STYLE[:PREFIX[:START]])",
+                entry
+            );
+        };
+        let style = match style.trim() {
+            "D" | "R" | "r" | "A" | "a" => Some(style.trim()),
+            "" | "-" => None,
+            other => bail!(
+                "Unknown label style: '{}' (use D, r, R, a, A or - for prefix only)",
+                other
+            ),
+        };
+        let prefix = fields.next().unwrap_or_default();
+        let start: i64 = match fields.next() {
+            Some(start) => start
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&start| start >= 1)
+                .with_context(|| format!("Invalid label start in '{}'", entry))?,
+            None => 1,
+        };
+
+        let page_numbers = parse_page_ranges(pages, total_pages)?;
+        let (Some(&first), Some(&last)) = (page_numbers.first(), page_numbers.last()) else {
+            bail!("Label range '{}' selects no pages", entry);
+        };
+        if first < next_free {
+            bail!(
+                "Label range '{}' overlaps an earlier range; list ranges in page order",
+                entry
+            );
+        }
+        if first > next_free {
+            ranges.insert(
+                next_free as i64 - 1,
+                label_range(Some("D"), "", next_free as i64),
+            );
+        }
+        ranges.insert(first as i64 - 1, label_range(style, prefix, start));
+        next_free = last + 1;
+    }
+    if ranges.is_empty() {
+        bail!("No label ranges given");
+    }
+    if next_free <= total_pages {
+        ranges.insert(
+            next_free as i64 - 1,
+            label_range(Some("D"), "", next_free as i64),
+        );
+    }
+    Ok(ranges)
+}
+
+/// The spec string that recreates `ranges`.
+fn label_spec(ranges: &BTreeMap<i64, Dictionary>, total_pages: usize) -> String {
+    let keys: Vec<i64> = ranges.keys().copied().collect();
+    ranges
+        .iter()
+        .enumerate()
+        .map(|(index, (&first, range))| {
+            let end = keys.get(index + 1).map(|next| next.to_string());
+            let pages = match end {
+                Some(end) if end == (first + 1).to_string() => (first + 1).to_string(),
+                Some(end) => format!("{}-{}", first + 1, end),
+                None if first + 1 == total_pages as i64 => (first + 1).to_string(),
+                None => format!("{}-", first + 1),
+            };
+            let style = range
+                .get(b"S")
+                .and_then(Object::as_name)
+                .map(|style| String::from_utf8_lossy(style).into_owned())
+                .unwrap_or_else(|_| "-".to_string());
+            let prefix = range
+                .get(b"P")
+                .ok()
+                .and_then(|prefix| decode_text_string(prefix).ok())
+                .unwrap_or_default();
+            let start = range.get(b"St").and_then(Object::as_i64).unwrap_or(1);
+            let mut spec = format!("{}:{}", pages, style);
+            if !prefix.is_empty() || start != 1 {
+                spec.push_str(&format!(":{}", prefix));
+            }
+            if start != 1 {
+                spec.push_str(&format!(":{}", start));
+            }
+            spec
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn print_labels(doc: &Document, path: &Path) {
+    let ranges = label_ranges(doc);
+    let total_pages = doc.get_pages().len();
+    println!("📄 {}", path.display());
+    let Some(labels) = page_labels(doc) else {
+        println!("   (no page labels; pages are numbered 1-{})", total_pages);
+        return;
+    };
+
+    let keys: Vec<usize> = ranges.keys().map(|&first| first as usize).collect();
+    for (index, &first) in keys.iter().enumerate() {
+        let last = keys
+            .get(index + 1)
+            .map_or(total_pages, |&next| next)
+            .min(total_pages);
+        if first >= last {
+            continue;
+        }
+        if last - first == 1 {
+            println!("   Page {}: {}", last, labels[first]);
+        } else {
+            println!(
+                "   Pages {}-{}: {} … {}",
+                first + 1,
+                last,
+                labels[first],
+                labels[last - 1]
+            );
         }
     }
+    println!("   Spec: {}", label_spec(&ranges, total_pages));
+}
 
-    pages.sort_unstable();
-    pages.dedup();
-    Ok(pages)
+fn edit_labels(
+    input: &Path,
+    output: &Path,
+    spec: Option<&str>,
+    clear: bool,
+    password: Option<&str>,
+) -> Result<()> {
+    let mut doc = load_pdf(input, password)?;
+    let total_pages = doc.get_pages().len();
+
+    let ranges = match spec {
+        Some(spec) => parse_label_spec(spec, total_pages)?,
+        None if clear => BTreeMap::new(),
+        None => {
+            print_labels(&doc, input);
+            return Ok(());
+        }
+    };
+
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
+    set_label_ranges(&mut doc, &ranges)?;
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    print_labels(&doc, &current_output);
+    println!("✓ Page labels saved: {}", current_output.display());
+    Ok(())
 }
 
-fn resolve_output_conflict(output: &Path, is_directory: bool) -> Result<Option<PathBuf>> {
-    let current_output = output.to_path_buf();
+fn decrypt_pdf(input: &Path, output: &Path, password: Option<&str>) -> Result<()> {
+    let mut doc = load_pdf(input, password)?;
+    let Some(dict) = encryption_dict(&doc) else {
+        bail!("{} is not encrypted", input.display());
+    };
 
-    if !current_output.exists() {
-        return Ok(Some(current_output));
-    }
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
 
-    let output_type = if is_directory { "directory" } else { "file" };
-    print!(
-        "⚠️ Output {} '{}' already exists. Action? (Y=overwrite, R=rename, N=abort): ",
-        output_type,
+    doc.encryption_state = None;
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!(
+        "✓ Removed {} encryption: {}",
+        encryption_method(&dict),
         current_output.display()
     );
-    io::stdout().flush()?;
-    let mut choice = String::new();
-    io::stdin().read_line(&mut choice)?;
-    match choice.trim().to_lowercase().as_str() {
-        "y" | "yes" => Ok(Some(current_output)),
-        "n" | "no" => {
-            println!("Aborted.");
-            Ok(None)
-        }
-        "r" | "rename" => {
-            let prompt = if is_directory {
-                "Enter a new output folder: "
-            } else {
-                "Enter a new filename or folder (e.g., report.pdf or C:\\output): "
-            };
-            print!("{}", prompt);
-            io::stdout().flush()?;
-            let mut new_path = String::new();
-            io::stdin().read_line(&mut new_path)?;
-            let trimmed = new_path.trim();
-            if trimmed.is_empty() {
-                println!("Empty path. Aborted.");
-                return Ok(None);
-            }
-            let mut new_output = PathBuf::from(trimmed);
+    Ok(())
+}
 
-            if !is_directory {
-                if new_output.is_dir() {
-                    if let Some(filename) = output.file_name() {
-                        new_output.push(filename);
-                    }
-                }
-            }
+fn encrypt_pdf(
+    input: &Path,
+    output: &Path,
+    password: Option<&str>,
+    settings: &EncryptArgs,
+) -> Result<()> {
+    validate_encryption(settings)?;
+    let mut doc = load_pdf(input, password)?;
+    doc.encryption_state = None;
 
-            if new_output.exists() {
-                let msg = if is_directory {
-                    format!(
-                        "❌ Output folder '{}' already exists. Aborted to prevent overwrite.",
-                        new_output.display()
-                    )
-                } else {
-                    format!(
-                        "❌ Output '{}' already exists. Aborted to prevent overwrite.",
-                        new_output.display()
-                    )
-                };
-                println!("{}", msg);
-                return Ok(None);
-            }
+    let Some(current_output) = prepare_output_file(output)? else {
+        return Ok(());
+    };
 
-            Ok(Some(new_output))
-        }
-        _ => {
-            println!("Invalid choice. Aborted.");
-            Ok(None)
-        }
-    }
+    let method = encrypt_document(&mut doc, settings)?;
+    doc.save(&current_output)
+        .with_context(|| format!("Failed to save: {}", current_output.display()))?;
+    println!("✓ Encrypted with {}: {}", method, current_output.display());
+    Ok(())
 }
 
-fn merge_pdfs(inputs: &[PathBuf], output: &Path) -> Result<()> {
+fn merge_pdfs(
+    inputs: &[PathBuf],
+    output: &Path,
+    options: &MergeOptions,
+    password: Option<&str>,
+) -> Result<()> {
+    let metadata_from = options.metadata_from;
+    let pdf_version = options.pdf_version.as_deref();
+    let separator = options.separator.as_deref();
+
     if inputs.is_empty() {
         bail!("No input files provided");
     }
+    if metadata_from < 1 || metadata_from > inputs.len() {
+        bail!(
+            "--metadata-from {} is out of range ({} input(s))",
+            metadata_from,
+            inputs.len()
+        );
+    }
+    if let Some(version) = pdf_version {
+        parse_pdf_version(version)?;
+    }
+    let normalize_size = options
+        .normalize_size
+        .as_deref()
+        .map(parse_page_size)
+        .transpose()?;
+    let normalize_mode = parse_resize_mode(&options.normalize_mode)?;
+    let separator_size = options
+        .separator_size
+        .as_deref()
+        .map(parse_page_size)
+        .transpose()?;
+    if separator_size.is_some() && separator != Some("blank") {
+        bail!("--separator-size only applies to --separator blank");
+    }
+    let encryption = output_encryption(&options.encryption, pdf_version)?;
+    if let Some(&index) = options
+        .collate
+        .reverse
+        .iter()
+        .find(|&&index| index < 1 || index > inputs.len())
+    {
+        bail!(
+            "--reverse {} is out of range ({} input(s))",
+            index,
+            inputs.len()
+        );
+    }
     if inputs.len() == 1 {
         println!("⚠️ Note: Only one input file provided. This will copy/repair the PDF.");
     }
 
-    let Some(current_output) = resolve_output_conflict(output, false)? else {
+    let Some(current_output) = prepare_output_file(output)? else {
         return Ok(());
     };
 
-    let current_output = match current_output.extension().and_then(|e| e.to_str()) {
-        Some("pdf") => current_output,
-        _ => current_output.with_extension("pdf"),
+    let separator_doc = match separator {
+        Some(path) if path != "blank" => Some(load_pdf(Path::new(path), password)?),
+        _ => None,
     };
 
     println!("Merging {} PDF(s)...", inputs.len());
-    let mut merged = Document::with_version("1.5");
-    let mut page_refs: Vec<Object> = Vec::new();
+    let mut merged = Document::new();
+    let mut source_version = (1, 0);
+    let mut input_pages: Vec<Vec<ObjectId>> = Vec::with_capacity(inputs.len());
+    let mut inherited_info = Vec::new();
+    let mut titles = Vec::with_capacity(inputs.len());
+    let mut page_label_numbers: HashMap<ObjectId, (Dictionary, i64)> = HashMap::new();
 
-    for input in inputs {
+    for (index, input) in inputs.iter().enumerate() {
         println!("  Processing: {}", input.display());
-        let doc = Document::load(input)
-            .with_context(|| format!("Failed to load PDF: {}", input.display()))?;
+        let doc = load_pdf(input, password)?;
         if doc.get_pages().is_empty() {
             bail!("Input PDF has no pages: {}", input.display());
         }
+        let info = info_entries(&doc);
+        let title = info
+            .iter()
+            .find(|(key, value)| key == "Title" && !value.trim().is_empty())
+            .map(|(_, value)| value.clone())
+            .or_else(|| {
+                input
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| input.display().to_string());
+        titles.push(title);
+        if index + 1 == metadata_from {
+            inherited_info = info;
+        }
+        source_version = source_version.max(document_version(&doc));
 
-        let mut id_map: HashMap<ObjectId, ObjectId> = HashMap::new();
-        let mut new_id = merged.max_id + 1;
+        let id_map = import_objects(&mut merged, &doc)?;
 
-        for &old_id in doc.objects.keys() {
-            id_map.insert(old_id, (new_id, 0));
-            new_id += 1;
+        let mut pages: Vec<ObjectId> = doc.page_iter().map(|id| id_map[&id]).collect();
+        if options.labels {
+            let mut labels = label_ranges(&doc);
+            for range in labels.values_mut() {
+                let mut object = Object::Dictionary(std::mem::take(range));
+                update_references_in_object(&mut object, &id_map)?;
+                if let Object::Dictionary(dict) = object {
+                    *range = dict;
+                }
+            }
+            for (page_index, &page_id) in pages.iter().enumerate() {
+                page_label_numbers.insert(page_id, page_label_number(&labels, page_index as i64));
+            }
         }
-        merged.max_id = new_id - 1;
-
-        for (&old_id, obj) in doc.objects.iter() {
-            let new_id = id_map[&old_id];
-            let mut cloned = obj.clone();
-            update_references_in_object(&mut cloned, &id_map)?;
-            merged.objects.insert(new_id, cloned);
+        if options.collate.reverse.contains(&(index + 1)) {
+            pages.reverse();
         }
+        input_pages.push(pages);
+    }
 
-        for (_, &page_id) in doc.get_pages().iter() {
-            if let Some(&new_page_id) = id_map.get(&page_id) {
-                page_refs.push(Object::Reference(new_page_id));
+    let mut page_ids: Vec<ObjectId> = Vec::new();
+    if options.interleave {
+        let counts: Vec<usize> = input_pages.iter().map(Vec::len).collect();
+        if !options.allow_uneven && counts.iter().any(|&count| count != counts[0]) {
+            let details: Vec<String> = inputs
+                .iter()
+                .zip(&counts)
+                .map(|(input, count)| format!("{} ({} pages)", input.display(), count))
+                .collect();
+            bail!(
+                "Page counts differ: {}. Use --allow-uneven to append the remainder",
+                details.join(", ")
+            );
+        }
+        let longest = counts.iter().copied().max().unwrap_or(0);
+        for position in 0..longest {
+            for pages in &input_pages {
+                if let Some(&page_id) = pages.get(position) {
+                    page_ids.push(page_id);
+                }
+            }
+        }
+    } else {
+        for (index, pages) in input_pages.iter().enumerate() {
+            if index > 0 {
+                match &separator_doc {
+                    Some(separator_doc) => {
+                        let id_map = import_objects(&mut merged, separator_doc)?;
+                        page_ids.extend(separator_doc.page_iter().map(|id| id_map[&id]));
+                    }
+                    None if separator.is_some() => {
+                        let neighbour = page_ids.last().copied();
+                        page_ids.push(add_blank_page(&mut merged, separator_size, neighbour));
+                    }
+                    None => {}
+                }
             }
+            page_ids.extend(pages);
         }
     }
 
     let mut pages_dict = Dictionary::new();
     pages_dict.set(b"Type".to_vec(), Object::Name(b"Pages".to_vec()));
-    pages_dict.set(b"Kids".to_vec(), Object::Array(page_refs.clone()));
-    pages_dict.set(b"Count".to_vec(), Object::Integer(page_refs.len() as i64));
     let pages_id = merged.add_object(pages_dict);
 
     let mut catalog = Dictionary::new();
@@ -457,9 +6803,67 @@ fn merge_pdfs(inputs: &[PathBuf], output: &Path) -> Result<()> {
     let catalog_id = merged.add_object(catalog);
 
     merged.trailer.set("Root", Object::Reference(catalog_id));
+    set_page_order(&mut merged, &page_ids)?;
+    if let Some(size) = normalize_size {
+        let mut transformed = HashSet::new();
+        for (index, &page_id) in page_ids.iter().enumerate() {
+            resize_page(&mut merged, page_id, size, normalize_mode, &mut transformed)
+                .with_context(|| format!("Failed to resize page {}", index + 1))?;
+        }
+        println!(
+            "Normalized {} page(s) to {:.0} x {:.0} pt",
+            page_ids.len(),
+            size.0,
+            size.1
+        );
+    }
+    if options.labels {
+        // Separator pages belong to no input and map to None.
+        let numbered: Vec<Option<(Dictionary, i64)>> = page_ids
+            .iter()
+            .map(|id| page_label_numbers.remove(id))
+            .collect();
+        set_label_ranges(&mut merged, &label_ranges_for_pages(&numbered))?;
+    }
+    if options.bookmarks {
+        // Point at whichever of the input's pages comes first in the
+        // output; with --reverse that is the input's last page.
+        let page_numbers: HashMap<ObjectId, u32> = merged
+            .get_pages()
+            .into_iter()
+            .map(|(num, id)| (id, num))
+            .collect();
+        let entries: Vec<OutlineEntry> = titles
+            .into_iter()
+            .zip(&input_pages)
+            .map(|(title, pages)| OutlineEntry {
+                title,
+                page: pages
+                    .iter()
+                    .filter_map(|id| page_numbers.get(id))
+                    .min()
+                    .copied(),
+                view: Vec::new(),
+                uri: None,
+                level: 0,
+                bold: false,
+                italic: false,
+                color: None,
+                open: false,
+            })
+            .collect();
+        write_outline(&mut merged, &entries, false)?;
+        println!("Added {} bookmark(s)", entries.len());
+    }
+    apply_output_metadata(&mut merged, &inherited_info, &options.metadata)?;
+    set_output_version(&mut merged, source_version, pdf_version)?;
     merged
         .trailer
         .set("Size", Object::Integer(merged.max_id as i64 + 1));
+    if let Some(settings) = encryption {
+        let method = encrypt_document(&mut merged, settings)?;
+        println!("Encrypted with {}", method);
+    }
 
     merged
         .save(&current_output)
@@ -468,13 +6872,25 @@ fn merge_pdfs(inputs: &[PathBuf], output: &Path) -> Result<()> {
     Ok(())
 }
 
-fn split_pdf(input: &Path, output: &Path, pages_spec: Option<&str>) -> Result<()> {
+fn split_pdf(
+    input: &Path,
+    output: &Path,
+    pages_spec: Option<&str>,
+    pdf_version: Option<&str>,
+    metadata: &MetadataArgs,
+    password: Option<&str>,
+    encryption: &OutputEncryptionArgs,
+) -> Result<()> {
     if !input.exists() {
         bail!("Input file does not exist: {}", input.display());
     }
     if !input.is_file() {
         bail!("Input is not a file: {}", input.display());
     }
+    if let Some(version) = pdf_version {
+        parse_pdf_version(version)?;
+    }
+    let encryption = output_encryption(encryption, pdf_version)?;
 
     let Some(current_output) = resolve_output_conflict(output, true)? else {
         return Ok(());
@@ -484,21 +6900,23 @@ fn split_pdf(input: &Path, output: &Path, pages_spec: Option<&str>) -> Result<()
         for entry in std::fs::read_dir(&current_output)? {
             let entry = entry?;
             let path = entry.path();
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if name.starts_with("page_") && name.ends_with(".pdf") {
-                    std::fs::remove_file(path)?;
-                }
+            if let Some(name) = path.file_name().and_then(|n| n.to_str())
+                && name.starts_with("page_")
+                && name.ends_with(".pdf")
+            {
+                std::fs::remove_file(path)?;
             }
         }
     }
 
-    let doc = Document::load(input)
-        .with_context(|| format!("Failed to load PDF: {}", input.display()))?;
+    let doc = load_pdf(input, password)?;
     if doc.get_pages().is_empty() {
         bail!("Input PDF has no pages: {}", input.display());
     }
     let total_pages = doc.get_pages().len();
     println!("PDF has {} pages.", total_pages);
+    let inherited_info = info_entries(&doc);
+    let source_version = document_version(&doc);
 
     let page_numbers = if let Some(mut spec) = pages_spec.map(|s| s.to_string()) {
         loop {
@@ -547,8 +6965,8 @@ fn split_pdf(input: &Path, output: &Path, pages_spec: Option<&str>) -> Result<()
             if obj_id == (0, 0) {
                 continue;
             }
-            if !cloned_objects.contains_key(&obj_id) {
-                cloned_objects.insert(obj_id, doc.get_object(obj_id)?.clone());
+            if let Entry::Vacant(entry) = cloned_objects.entry(obj_id) {
+                entry.insert(doc.get_object(obj_id)?.clone());
             }
         }
     }
@@ -582,7 +7000,7 @@ fn split_pdf(input: &Path, output: &Path, pages_spec: Option<&str>) -> Result<()
         let page_id = all_page_ids[page_num - 1];
         let referenced = &page_dependencies[&page_id];
 
-        let mut single = Document::with_version("1.5");
+        let mut single = Document::new();
         let mut id_map = HashMap::new();
         let mut new_id = 1u32;
 
@@ -621,9 +7039,14 @@ fn split_pdf(input: &Path, output: &Path, pages_spec: Option<&str>) -> Result<()
         let catalog_id = single.add_object(catalog);
 
         single.trailer.set("Root", Object::Reference(catalog_id));
+        apply_output_metadata(&mut single, &inherited_info, metadata)?;
+        set_output_version(&mut single, source_version, pdf_version)?;
         single
             .trailer
             .set("Size", Object::Integer(single.max_id as i64 + 1));
+        if let Some(settings) = encryption {
+            encrypt_document(&mut single, settings)?;
+        }
 
         let out_path = current_output.join(format!("page_{:03}.pdf", page_num));
         single.save(&out_path).with_context(|| {
@@ -726,3 +7149,729 @@ fn collect_from_object(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A Letter-size document with one page per entry of `texts`, each
+    /// showing its text in Helvetica from a shared /Resources dictionary.
+    fn sample_document(texts: &[&str]) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(standard_font("Helvetica"));
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+        let kids: Vec<Object> = texts
+            .iter()
+            .map(|text| {
+                let content = format!("BT /F1 24 Tf 72 700 Td ({}) Tj ET", text);
+                let content_id = doc.add_object(Stream::new(Dictionary::new(), content.into()));
+                let page_id = doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                });
+                Object::Reference(page_id)
+            })
+            .collect();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn pages_root(doc: &Document) -> ObjectId {
+        doc.catalog()
+            .unwrap()
+            .get(b"Pages")
+            .unwrap()
+            .as_reference()
+            .unwrap()
+    }
+
+    /// A fresh path in the temporary directory that no other test uses.
+    fn temp_path(name: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "pdfer-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    fn save_sample(texts: &[&str]) -> PathBuf {
+        let path = temp_path("input.pdf");
+        sample_document(texts).save(&path).unwrap();
+        path
+    }
+
+    /// The text of every page of the PDF at `path`, in page order.
+    fn page_texts(path: &Path) -> Vec<String> {
+        let doc = Document::load(path).unwrap();
+        doc.get_pages()
+            .keys()
+            .map(|&page| doc.extract_text(&[page]).unwrap().trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn grep_counts_matches_per_page() {
+        let input = save_sample(&["Alpha invoice", "Bravo", "Charlie Invoice"]);
+        let files = [input];
+        assert_eq!(grep_pdfs(&files, "invoice", false, false, None).unwrap(), 1);
+        assert_eq!(grep_pdfs(&files, "invoice", true, false, None).unwrap(), 2);
+        assert_eq!(grep_pdfs(&files, "invoice", true, true, None).unwrap(), 1);
+        assert_eq!(grep_pdfs(&files, "Delta", false, false, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn grep_context_is_bounded_by_words_and_lines() {
+        let text = format!("{}needle{}", "word ".repeat(20), " more".repeat(20));
+        let start = text.find("needle").unwrap();
+        let (from, to) = context_window(&text, start, start + 6);
+        let window = &text[from..to];
+        assert!(window.starts_with("word ") && window.ends_with("more"));
+        assert!(window.chars().count() <= 6 + 2 * GREP_CONTEXT);
+
+        let text = "first line\nthe needle here\nlast line";
+        let start = text.find("needle").unwrap();
+        let (from, to) = context_window(text, start, start + 6);
+        assert_eq!(&text[from..to], "the needle here");
+    }
+
+    #[test]
+    fn pdf_dates_to_iso() {
+        assert_eq!(
+            pdf_date_to_iso("D:20240315143000+01'00'").as_deref(),
+            Some("2024-03-15T14:30:00+01:00")
+        );
+        assert_eq!(
+            pdf_date_to_iso("D:20240315143000Z").as_deref(),
+            Some("2024-03-15T14:30:00Z")
+        );
+        assert_eq!(
+            pdf_date_to_iso("2024").as_deref(),
+            Some("2024-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            pdf_date_to_iso("D:202403-05").as_deref(),
+            Some("2024-03-01T00:00:00-05:00")
+        );
+        assert_eq!(pdf_date_to_iso("D:20x"), None);
+        assert_eq!(pdf_date_to_iso("D:20240315?"), None);
+    }
+
+    #[test]
+    fn xmp_update_keeps_other_schemas() {
+        let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<r:RDF xmlns:r="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<r:Description r:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/"
+    xmlns:d="http://purl.org/dc/elements/1.1/" d:format="application/pdf">
+   <pdfaid:part>2</pdfaid:part>
+   <d:title><r:Alt><r:li xml:lang="x-default">Old</r:li></r:Alt></d:title>
+</r:Description>
+</r:RDF>
+</x:xmpmeta>"#;
+        let title = "   <dc:title>New</dc:title>\n";
+        let updated = update_xmp_packet(packet, title, "", "").unwrap();
+        assert!(updated.contains("<pdfaid:part>2</pdfaid:part>"));
+        assert!(updated.contains(r#"d:format="application/pdf""#));
+        assert!(!updated.contains("Old"));
+        assert!(updated.contains("<r:Description r:about=\"\"\n"));
+        assert!(updated.contains(title));
+        assert!(updated.trim_end().ends_with("</r:RDF>\n</x:xmpmeta>"));
+
+        let cleared = update_xmp_packet(packet, "", "", "").unwrap();
+        assert!(!cleared.contains("Old") && !cleared.contains("xmlns:dc="));
+        assert!(update_xmp_packet("<x:xmpmeta/>", title, "", "").is_none());
+    }
+
+    #[test]
+    fn sanitize_removes_scripts_and_open_actions() {
+        let mut doc = sample_document(&["One", "Two"]);
+        let script = doc.add_object(dictionary! {
+            "Type" => "Action",
+            "S" => "JavaScript",
+            "JS" => Object::string_literal("app.alert('hi')"),
+        });
+        let names = doc.add_object(dictionary! {
+            "JavaScript" => dictionary! {
+                "Names" => vec![Object::string_literal("init"), script.into()],
+            },
+        });
+        let catalog = doc.catalog_mut().unwrap();
+        catalog.set("OpenAction", script);
+        catalog.set("Names", names);
+        // Damaged files may point at objects that do not exist.
+        catalog.set("Dangling", Object::Reference((999, 0)));
+        let input = temp_path("input.pdf");
+        doc.save(&input).unwrap();
+
+        let output = temp_path("sanitized.pdf");
+        sanitize_pdf(&input, &output, None).unwrap();
+
+        let doc = Document::load(&output).unwrap();
+        let catalog = doc.catalog().unwrap();
+        assert!(catalog.get(b"OpenAction").is_err());
+        let names = doc.get_dictionary(catalog.get(b"Names").unwrap().as_reference().unwrap());
+        assert!(names.unwrap().get(b"JavaScript").is_err());
+        assert!(doc.objects.values().all(|object| {
+            object
+                .as_dict()
+                .map_or(true, |dict| active_content_category(dict).is_none())
+        }));
+        assert_eq!(doc.get_pages().len(), 2);
+    }
+
+    #[test]
+    fn rotate_adds_to_inherited_rotation() {
+        let mut doc = sample_document(&["One", "Two", "Three"]);
+        let pages_id = pages_root(&doc);
+        doc.get_dictionary_mut(pages_id).unwrap().set("Rotate", 90);
+        let input = temp_path("input.pdf");
+        doc.save(&input).unwrap();
+
+        let output = temp_path("rotated.pdf");
+        rotate_pdf(&input, &output, 90, false, Some("2-3"), None).unwrap();
+        let doc = Document::load(&output).unwrap();
+        let rotations: Vec<i64> = doc.page_iter().map(|id| page_rotation(&doc, id)).collect();
+        assert_eq!(rotations, [90, 180, 180]);
+
+        let absolute = temp_path("absolute.pdf");
+        rotate_pdf(&output, &absolute, -90, true, Some("1"), None).unwrap();
+        let doc = Document::load(&absolute).unwrap();
+        let rotations: Vec<i64> = doc.page_iter().map(|id| page_rotation(&doc, id)).collect();
+        assert_eq!(rotations, [270, 180, 180]);
+        assert_eq!(page_texts(&absolute), ["One", "Two", "Three"]);
+    }
+
+    #[test]
+    fn delete_prunes_pages_and_their_links() {
+        let mut doc = sample_document(&["One", "Two", "Three"]);
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        let removed_content = doc
+            .get_dictionary(pages[1])
+            .unwrap()
+            .get(b"Contents")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let goto = doc.add_object(dictionary! {
+            "S" => "GoTo",
+            "D" => vec![pages[2].into(), "Fit".into()],
+        });
+        let dead_link = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+            "Dest" => vec![pages[1].into(), "Fit".into()],
+        });
+        let live_link = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "Rect" => vec![0.into(), 10.into(), 10.into(), 20.into()],
+            "A" => goto,
+        });
+        doc.get_dictionary_mut(pages[0])
+            .unwrap()
+            .set("Annots", vec![dead_link.into(), live_link.into()]);
+        let outlines = doc.new_object_id();
+        let to_removed = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Two"),
+            "Parent" => outlines,
+            "Dest" => vec![pages[1].into(), "XYZ".into(), Object::Null, Object::Null, Object::Null],
+        });
+        let to_kept = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Three"),
+            "Parent" => outlines,
+            "Prev" => to_removed,
+            "A" => dictionary! { "S" => "GoTo", "D" => vec![pages[2].into(), "Fit".into()] },
+        });
+        doc.get_dictionary_mut(to_removed)
+            .unwrap()
+            .set("Next", to_kept);
+        doc.objects.insert(
+            outlines,
+            Object::Dictionary(dictionary! {
+                "Type" => "Outlines",
+                "First" => to_removed,
+                "Last" => to_kept,
+                "Count" => 2,
+            }),
+        );
+        doc.catalog_mut().unwrap().set("Outlines", outlines);
+        let input = temp_path("input.pdf");
+        doc.save(&input).unwrap();
+
+        let output = temp_path("deleted.pdf");
+        delete_pages(&input, &output, "2", None).unwrap();
+
+        let doc = Document::load(&output).unwrap();
+        assert_eq!(page_texts(&output), ["One", "Three"]);
+        assert!(doc.get_object(removed_content).is_err());
+        assert!(doc.get_object(dead_link).is_err());
+        let first = doc.page_iter().next().unwrap();
+        let annots = doc.get_dictionary(first).unwrap().get(b"Annots").unwrap();
+        assert_eq!(
+            annots.as_array().unwrap(),
+            &vec![Object::Reference(live_link)]
+        );
+
+        let item = doc.get_dictionary(to_removed).unwrap();
+        assert!(item.get(b"Dest").is_err() && item.get(b"A").is_err());
+        let action = doc.get_dictionary(to_kept).unwrap().get(b"A").unwrap();
+        let dest = action
+            .as_dict()
+            .unwrap()
+            .get(b"D")
+            .unwrap()
+            .as_array()
+            .unwrap();
+        assert_eq!(dest[0], Object::Reference(doc.page_iter().nth(1).unwrap()));
+    }
+
+    #[test]
+    fn page_sequence_lists_and_ranges() {
+        assert_eq!(parse_page_sequence("1,3-5", 10).unwrap(), [1, 3, 4, 5]);
+        assert_eq!(parse_page_sequence("8-", 10).unwrap(), [8, 9, 10]);
+        assert_eq!(parse_page_sequence(" 2 , 2 ", 3).unwrap(), [2, 2]);
+        assert!(parse_page_sequence("5-3", 10).is_err());
+        assert!(parse_page_sequence("0", 10).is_err());
+        assert!(parse_page_sequence("11", 10).is_err());
+        assert!(parse_page_sequence("-4", 10).is_err());
+        assert!(parse_page_sequence("1", 0).is_err());
+    }
+
+    #[test]
+    fn reorder_keeps_shared_resources_shared() {
+        let mut doc = sample_document(&["One", "Two", "Three"]);
+        let pages_id = pages_root(&doc);
+        let resources = doc
+            .get_dictionary(pages_id)
+            .unwrap()
+            .get(b"Resources")
+            .unwrap()
+            .clone();
+        let input = temp_path("input.pdf");
+        doc.save(&input).unwrap();
+
+        let output = temp_path("reordered.pdf");
+        reorder_pages(&input, &output, Some("3,1,2"), false, false, None).unwrap();
+        assert_eq!(page_texts(&output), ["Three", "One", "Two"]);
+        let doc = Document::load(&output).unwrap();
+        for page in doc.page_iter() {
+            let page = doc.get_dictionary(page).unwrap();
+            assert_eq!(page.get(b"Resources").unwrap(), &resources);
+        }
+
+        let repeated = temp_path("repeated.pdf");
+        assert!(
+            reorder_pages(
+                &input,
+                &temp_path("dup.pdf"),
+                Some("1,1,2,3"),
+                false,
+                false,
+                None
+            )
+            .is_err()
+        );
+        reorder_pages(&input, &repeated, Some("1,1,2,3"), true, true, None).unwrap();
+        assert_eq!(page_texts(&repeated), ["Three", "Two", "One", "One"]);
+        assert!(
+            reorder_pages(
+                &input,
+                &temp_path("short.pdf"),
+                Some("1,2"),
+                false,
+                false,
+                None
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn insert_places_pages_at_one_based_positions() {
+        let input = save_sample(&["One", "Two"]);
+        let source = save_sample(&["A", "B", "C"]);
+        let from = format!("{}:3,1", source.display());
+
+        let output = temp_path("inserted.pdf");
+        insert_pages(&input, &output, 2, &from, None).unwrap();
+        assert_eq!(page_texts(&output), ["One", "C", "A", "Two"]);
+
+        let appended = temp_path("appended.pdf");
+        insert_pages(&input, &appended, 3, &source.display().to_string(), None).unwrap();
+        assert_eq!(page_texts(&appended), ["One", "Two", "A", "B", "C"]);
+
+        let error = insert_pages(&input, &temp_path("zero.pdf"), 0, &from, None).unwrap_err();
+        assert!(error.to_string().contains("1-based"));
+        assert!(insert_pages(&input, &temp_path("far.pdf"), 4, &from, None).is_err());
+    }
+
+    fn close(actual: f32, expected: f32) -> bool {
+        (actual - expected).abs() < 0.01
+    }
+
+    #[test]
+    fn lengths_convert_to_points() {
+        assert!(close(parse_length("72").unwrap(), 72.0));
+        assert!(close(parse_length("10 pt").unwrap(), 10.0));
+        assert!(close(parse_length("1in").unwrap(), 72.0));
+        assert!(close(parse_length("25.4mm").unwrap(), 72.0));
+        assert!(close(parse_length("2.54CM").unwrap(), 72.0));
+        assert!(parse_length("5furlongs").is_err());
+        assert!(parse_length("mm").is_err());
+    }
+
+    #[test]
+    fn page_sizes_by_name_and_dimensions() {
+        let (width, height) = parse_page_size("A4").unwrap();
+        assert!(close(width, 595.28) && close(height, 841.89));
+        let (width, height) = parse_page_size("a4-landscape").unwrap();
+        assert!(close(width, 841.89) && close(height, 595.28));
+        let (width, height) = parse_page_size("210x297mm").unwrap();
+        assert!((width - 595.28).abs() < 0.1 && (height - 841.89).abs() < 0.1);
+        assert_eq!(parse_page_size("612x792").unwrap(), (612.0, 792.0));
+        assert!(parse_page_size("0x792").is_err());
+        assert!(parse_page_size("Napkin").is_err());
+    }
+
+    #[test]
+    fn crop_is_clamped_to_the_media_box() {
+        let input = save_sample(&["One", "Two"]);
+        let crop_box = |path: &Path| -> Vec<[f32; 4]> {
+            let doc = Document::load(path).unwrap();
+            doc.page_iter()
+                .map(|id| page_box(&doc, id, b"CropBox"))
+                .collect()
+        };
+
+        let output = temp_path("cropped.pdf");
+        let margins = CropGeometry::Margins([72.0, 36.0, 72.0, 36.0]);
+        crop_pdf(
+            &input,
+            &output,
+            Some("2"),
+            &["crop".to_string()],
+            &margins,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            crop_box(&output),
+            [[0.0, 0.0, 612.0, 792.0], [36.0, 72.0, 576.0, 720.0]]
+        );
+
+        let clamped = temp_path("clamped.pdf");
+        let rect = CropGeometry::Rect([-100.0, 100.0, 400.0, 1000.0]);
+        crop_pdf(&input, &clamped, None, &["crop".to_string()], &rect, None).unwrap();
+        assert_eq!(crop_box(&clamped), [[0.0, 100.0, 400.0, 792.0]; 2]);
+
+        let outside = CropGeometry::Rect([700.0, 0.0, 900.0, 792.0]);
+        let crop = |geometry| {
+            let output = temp_path("empty.pdf");
+            crop_pdf(&input, &output, None, &["crop".to_string()], geometry, None)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(crop(&outside).contains("empty"));
+        assert!(crop(&CropGeometry::Margins([400.0, 0.0, 400.0, 0.0])).contains("empty"));
+    }
+
+    #[test]
+    fn form_matrix_places_lower_left_corner() {
+        let form = |bbox, rotation| PageForm {
+            id: (1, 0),
+            bbox,
+            rotation,
+        };
+        assert_eq!(
+            form_matrix(&form([10.0, 10.0, 110.0, 210.0], 0), 2.0, 0.0, 0.0),
+            [2.0, 0.0, 0.0, 2.0, -20.0, -20.0]
+        );
+        assert_eq!(
+            form_matrix(&form([0.0, 0.0, 100.0, 200.0], 90), 1.0, 10.0, 20.0),
+            [0.0, -1.0, 1.0, 0.0, 10.0, 120.0]
+        );
+        assert_eq!(
+            form_matrix(&form([0.0, 0.0, 100.0, 200.0], 180), 1.0, 0.0, 0.0),
+            [-1.0, 0.0, 0.0, -1.0, 100.0, 200.0]
+        );
+    }
+
+    #[test]
+    fn signature_order_nests_sheets() {
+        let pages: Vec<u32> = (1..=8).collect();
+        assert_eq!(
+            signature_order(&pages),
+            [[(8, 1), (2, 7)], [(6, 3), (4, 5)]]
+        );
+        assert!(signature_order(&[1, 2, 3]).is_empty());
+    }
+
+    #[test]
+    fn stamp_adds_resources_to_the_shared_dictionary_once() {
+        let mut doc = sample_document(&["One", "Two", "Three"]);
+        let resources = doc
+            .get_dictionary(pages_root(&doc))
+            .unwrap()
+            .get(b"Resources")
+            .unwrap()
+            .clone();
+        let input = temp_path("input.pdf");
+        doc.save(&input).unwrap();
+
+        let options = StampOptions {
+            source: StampSource::Text("DRAFT".to_string()),
+            underlay: false,
+            opacity: 0.5,
+            rotation: 45.0,
+            position: "center".to_string(),
+            margin: 0.0,
+            font: "Helvetica",
+            font_size: 48.0,
+            color: [1.0, 0.0, 0.0],
+            width: None,
+        };
+        let output = temp_path("stamped.pdf");
+        stamp_pdf(&input, &output, None, &options, None).unwrap();
+
+        let doc = Document::load(&output).unwrap();
+        for page in doc.page_iter() {
+            assert!(doc.get_dictionary(page).unwrap().get(b"Resources").is_err());
+        }
+        let shared = doc
+            .get_dictionary(resources.as_reference().unwrap())
+            .unwrap();
+        let names = |category: &[u8]| -> Vec<Vec<u8>> {
+            let entries = shared.get(category).unwrap().as_dict().unwrap();
+            entries.iter().map(|(name, _)| name.clone()).collect()
+        };
+        assert_eq!(names(b"Font"), [b"F1".to_vec(), b"PdferStamp".to_vec()]);
+        assert_eq!(names(b"ExtGState"), [b"PdferStampGS".to_vec()]);
+    }
+
+    #[test]
+    fn csv_round_trips_quoted_fields() {
+        let fields = ["in/z,\"q/a.pdf", "3", "line\nbreak", "plain"];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        let text = format!("file,pages,first,last\r\n{}\n\n", row.join(","));
+        assert_eq!(
+            parse_csv(&text),
+            [vec!["file", "pages", "first", "last"], fields.to_vec()]
+        );
+    }
+
+    /// Merge options as parsed from `pdfer merge` followed by `args`.
+    fn merge_options(args: &[&str]) -> MergeOptions {
+        let command = ["pdfer", "merge", "a.pdf"].iter().chain(args);
+        match Cli::try_parse_from(command).unwrap().command {
+            Some(Commands::Merge { options, .. }) => options,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn merge_bookmarks_each_input() {
+        let mut titled = sample_document(&["A1", "A2"]);
+        let info = titled.add_object(dictionary! { "Title" => text_string("Part A") });
+        titled.trailer.set("Info", info);
+        let first = temp_path("first.pdf");
+        titled.save(&first).unwrap();
+        let second = temp_path("second.pdf");
+        sample_document(&["B1"]).save(&second).unwrap();
+
+        let output = temp_path("merged.pdf");
+        let options = merge_options(&["--bookmarks"]);
+        merge_pdfs(&[first, second], &output, &options, None).unwrap();
+
+        assert_eq!(page_texts(&output), ["A1", "A2", "B1"]);
+        let outline = read_outline(&Document::load(&output).unwrap());
+        let entries: Vec<(&str, Option<u32>)> = outline
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.page))
+            .collect();
+        assert_eq!(entries, [("Part A", Some(1)), ("second", Some(3))]);
+    }
+
+    #[test]
+    fn outline_text_targets_and_styles() {
+        let entries = parse_outline_text(
+            "# contents\n\
+             Chapter 1 | 2 | bold open\n\
+             \x20 Section | 3 XYZ 72 700.5 null | italic #CC0000\n\
+             Appendix | -\n\
+             Errata | URI https://example.com/errata\n\
+             Q | A | 4\n",
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 5);
+
+        assert_eq!(entries[0].title, "Chapter 1");
+        assert_eq!(entries[0].page, Some(2));
+        assert!(entries[0].bold && entries[0].open && !entries[0].italic);
+
+        assert_eq!(entries[1].level, 1);
+        assert_eq!(entries[1].page, Some(3));
+        assert_eq!(
+            entries[1].view,
+            [
+                Object::Name(b"XYZ".to_vec()),
+                Object::Integer(72),
+                Object::Real(700.5),
+                Object::Null
+            ]
+        );
+        assert!(entries[1].italic);
+        assert_eq!(entries[1].color, Some([0.8, 0.0, 0.0]));
+
+        assert_eq!(entries[2].page, None);
+        assert_eq!(
+            entries[3].uri.as_deref(),
+            Some("https://example.com/errata")
+        );
+        assert_eq!(entries[4].title, "Q | A");
+        assert_eq!(entries[4].page, Some(4));
+
+        assert_eq!(
+            outline_to_text(&entries),
+            "Chapter 1 | 2 | bold open\n\
+             \x20 Section | 3 XYZ 72 700.5 null | italic #CC0000\n\
+             Appendix | -\n\
+             Errata | URI https://example.com/errata\n\
+             Q | A | 4\n"
+        );
+        assert!(parse_outline_text("Bad | 3 XYZ 1\n").is_err());
+        assert!(parse_outline_text("Bad | 3 | sparkly\n").is_err());
+    }
+
+    #[test]
+    fn destination_views() {
+        assert!(parse_view(&[]).unwrap().is_empty());
+        assert!(parse_view(&["Fit"]).unwrap().is_empty());
+        assert_eq!(
+            parse_view(&["FitH", "500"]).unwrap(),
+            [Object::Name(b"FitH".to_vec()), Object::Integer(500)]
+        );
+        assert!(parse_view(&["FitR", "1", "2"]).is_err());
+        assert!(parse_view(&["Zoom"]).is_err());
+        assert!(parse_view(&["XYZ", "a", "b", "c"]).is_err());
+    }
+
+    #[test]
+    fn outline_survives_a_save() {
+        let text = "Intro | 1 | bold\n\
+                    \x20 Detail | 2 FitH 500 | #0000FF\n\
+                    Link | URI https://example.com\n";
+        let mut doc = sample_document(&["One", "Two"]);
+        write_outline(&mut doc, &parse_outline_text(text).unwrap(), false).unwrap();
+        let path = temp_path("outlined.pdf");
+        doc.save(&path).unwrap();
+
+        let outline = read_outline(&Document::load(&path).unwrap());
+        assert_eq!(outline_to_text(&outline), text);
+    }
+
+    #[test]
+    fn label_spec_fills_gaps_with_decimals() {
+        let ranges = parse_label_spec("1-4:r,5-:D", 10).unwrap();
+        assert_eq!(ranges.keys().copied().collect::<Vec<_>>(), [0, 4]);
+        assert_eq!(ranges[&0].get(b"S").unwrap().as_name().unwrap(), b"r");
+        assert_eq!(ranges[&4].get(b"S").unwrap().as_name().unwrap(), b"D");
+
+        let ranges = parse_label_spec("3-4:A:App-:2", 6).unwrap();
+        assert_eq!(ranges.keys().copied().collect::<Vec<_>>(), [0, 2, 4]);
+        assert_eq!(ranges[&2].get(b"St").unwrap().as_i64().unwrap(), 2);
+        assert_eq!(
+            decode_text_string(ranges[&2].get(b"P").unwrap()).unwrap(),
+            "App-"
+        );
+        assert_eq!(ranges[&4].get(b"St").unwrap().as_i64().unwrap(), 5);
+        assert_eq!(label_spec(&ranges, 6), "1-2:D,3-4:A:App-:2,5-:D::5");
+
+        assert!(parse_label_spec("3-4:D,1-2:D", 6).is_err());
+        assert!(parse_label_spec("1:x", 6).is_err());
+        assert!(parse_label_spec("1-2", 6).is_err());
+    }
+
+    #[test]
+    fn merge_labels_keep_each_inputs_numbering() {
+        let mut front = sample_document(&["i", "ii", "1"]);
+        set_label_ranges(&mut front, &parse_label_spec("1-2:r,3-:D", 3).unwrap()).unwrap();
+        let first = temp_path("front.pdf");
+        front.save(&first).unwrap();
+        let second = save_sample(&["x", "y"]);
+
+        let output = temp_path("merged.pdf");
+        let options = merge_options(&["--labels"]);
+        merge_pdfs(&[first, second], &output, &options, None).unwrap();
+
+        let labels = page_labels(&Document::load(&output).unwrap()).unwrap();
+        assert_eq!(labels, ["i", "ii", "1", "1", "2"]);
+    }
+
+    #[test]
+    fn encrypted_output_opens_with_either_password() {
+        let input = save_sample(&["Classified", "Pages"]);
+        for aes128 in [false, true] {
+            let settings = EncryptArgs {
+                user_password: Some("secret".to_string()),
+                owner_password: Some("owner".to_string()),
+                allow: "print".to_string(),
+                aes128,
+            };
+            let encrypted = temp_path("encrypted.pdf");
+            encrypt_pdf(&input, &encrypted, None, &settings).unwrap();
+
+            assert!(Document::load(&encrypted).unwrap().is_encrypted());
+            assert!(load_pdf(&encrypted, None).is_err());
+            assert!(load_pdf(&encrypted, Some("wrong")).is_err());
+            let doc = load_pdf(&encrypted, Some("secret")).unwrap();
+            assert_eq!(doc.extract_text(&[1]).unwrap().trim(), "Classified");
+
+            let decrypted = temp_path("decrypted.pdf");
+            decrypt_pdf(&encrypted, &decrypted, Some("owner")).unwrap();
+            assert!(!Document::load(&decrypted).unwrap().is_encrypted());
+            assert_eq!(page_texts(&decrypted), ["Classified", "Pages"]);
+        }
+    }
+
+    #[test]
+    fn permissions_from_names() {
+        let permissions = parse_permissions("print, copy").unwrap();
+        assert!(permissions.contains(Permissions::PRINTABLE | Permissions::COPYABLE));
+        assert!(permissions.contains(Permissions::COPYABLE_FOR_ACCESSIBILITY));
+        assert!(!permissions.contains(Permissions::MODIFIABLE));
+
+        assert!(
+            parse_permissions("print-high")
+                .unwrap()
+                .contains(Permissions::PRINTABLE)
+        );
+        assert_eq!(
+            parse_permissions("none").unwrap(),
+            Permissions::COPYABLE_FOR_ACCESSIBILITY
+        );
+        assert_eq!(parse_permissions("ALL").unwrap(), Permissions::all());
+        assert!(parse_permissions("print,teleport").is_err());
+    }
+}